@@ -1,8 +1,12 @@
 use futures_executor::block_on;
+use futures_util::future::join_all;
 use git2::Repository;
-use indy_didresolver::did::DidUrl;
-use indy_didresolver::error::{DidIndyError, DidIndyResult};
-use indy_didresolver::resolver::Resolver;
+use indy_didresolver::did::{DidUrl, QueryParameter};
+use indy_didresolver::error::{
+    DidIndyError, DidIndyResult, INTERNAL_ERROR, INVALID_DID, METHOD_NOT_SUPPORTED, NOT_FOUND,
+    REPRESENTATION_NOT_SUPPORTED,
+};
+use indy_didresolver::resolver::{DereferencingResult, ResolutionResult, Resolver};
 use indy_vdr::pool::{helpers::perform_refresh, PoolBuilder, PoolTransactions, SharedPool};
 use regex::Regex;
 use rouille::Response;
@@ -10,13 +14,38 @@ use rouille::Response;
 use clap::Parser;
 use std::collections::HashMap;
 use std::fs;
+use std::io::Read;
 use std::path::PathBuf;
+use std::time::Duration;
 #[macro_use]
 extern crate log;
 
 static POOL_SIZE: Option<usize> = Some(32);
+// Maximum number of DID URLs dereferenced by one batch request
+static MAX_BATCH_SIZE: usize = 100;
+// Maximum size in bytes of the body of a batch request
+static MAX_BATCH_BODY_SIZE: u64 = 64 * 1024;
+// Media types of the body of a batch request
+static BATCH_MEDIA_TYPES: [&str; 2] = ["application/json", "text/plain"];
+static RESOLUTION_RESULT_CONTENT_TYPE: &str =
+    "application/ld+json;profile=\"https://w3id.org/did-resolution\"";
+// Media types a client may accept for the resolution result
+static SUPPORTED_MEDIA_TYPES: [&str; 4] = [
+    "*/*",
+    "application/*",
+    "application/json",
+    "application/ld+json",
+];
+// Media types of the DID document itself, served without resolution metadata
+static DID_DOCUMENT_MEDIA_TYPES: [&str; 2] = ["application/did+json", "application/did+ld+json"];
 type Resolvers = HashMap<String, Resolver<SharedPool>>;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Representation {
+    ResolutionResult,
+    DidDocument(&'static str),
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
@@ -40,6 +69,23 @@ pub struct Args {
         default_value = "pool_transactions_genesis.json"
     )]
     genesis_filename: String,
+    /// Reject ledger replies without state proof, the state proof is only checked for presence
+    /// and not verified
+    #[clap(long = "require-state-proof-present")]
+    require_state_proof_present: bool,
+    /// Number of cached resolution results per network, 0 disables the cache
+    #[clap(long = "cache-size", default_value_t = 0)]
+    cache_size: usize,
+    /// Seconds a resolved NYM, credential definition or revocation registry definition is cached
+    #[clap(long = "cache-ttl", default_value_t = 60)]
+    cache_ttl: u64,
+    /// Add created and next version of NYMs to the metadata, takes one ledger request per NYM
+    /// version
+    #[clap(long = "nym-version-metadata")]
+    nym_version_metadata: bool,
+    /// Add the endorser of NYMs to the metadata, takes one more ledger request per NYM
+    #[clap(long = "endorser-metadata")]
+    endorser_metadata: bool,
 }
 
 fn main() {
@@ -54,23 +100,69 @@ fn main() {
     rouille::start_server_with_pool(String::from("0.0.0.0:") + port, POOL_SIZE, move |request| {
         let url = request.url();
         debug!("incoming request: {}", url);
+
+        if request.method() == "POST" && url == "/1.0/identifiers" {
+            let body = match request.data() {
+                Some(data) => read_batch_body(request.header("Content-Type"), data),
+                None => Err((400, String::from("request body already read"))),
+            };
+            let body = match body {
+                Ok(body) => body,
+                Err((status_code, err)) => {
+                    info!("{}: unreadable request body: {}", status_code, err);
+                    return Response::text(err).with_status_code(status_code);
+                }
+            };
+            let (status_code, result) =
+                process_batch_request(&body, request.header("Accept"), &resolvers);
+            if status_code != 200 {
+                error!("{}: {}", status_code, result);
+            }
+            return Response::from_data(RESOLUTION_RESULT_CONTENT_TYPE, result)
+                .with_status_code(status_code);
+        }
+
+        let history_regex = Regex::new("/1.0/history/(.*)").unwrap();
+        if let Some(cap) = history_regex.captures(&url) {
+            let did = cap.get(1).unwrap().as_str();
+            let (status_code, result) = process_history_request(did, &resolvers);
+            if status_code != 200 {
+                error!("{}: {}", status_code, result);
+                return Response::from_data(RESOLUTION_RESULT_CONTENT_TYPE, result)
+                    .with_status_code(status_code);
+            }
+            // The versions are a plain JSON array, not a resolution result
+            return Response::from_data("application/json", result);
+        }
+
         let request_regex = Regex::new("/1.0/identifiers/(.*)").unwrap();
 
         let captures = request_regex.captures(&url);
         if let Some(cap) = captures {
             let did = cap.get(1).unwrap().as_str();
 
-            match process_request(did, &resolvers) {
-                Ok(result) => {
-                    info!("Serving for {}", &url);
-                    debug!("Serving DID Doc: {:?}", result);
-                    Response::text(result)
-                }
-                Err(err) => {
-                    error!("404: {:?}", err);
-                    Response::text("404").with_status_code(404)
+            let accept = request.header("Accept");
+            // Legacy identifiers are dereferenced in the namespace given as query parameter
+            let (status_code, content_type, result) = match request.get_param("namespace") {
+                Some(namespace) if !did.starts_with("did:") => {
+                    let (status_code, result) =
+                        process_legacy_request(did, &namespace, accept, &resolvers);
+                    (status_code, RESOLUTION_RESULT_CONTENT_TYPE, result)
                 }
+                _ => process_request(
+                    did,
+                    accept,
+                    request.get_param("transformKeys").as_deref(),
+                    &resolvers,
+                ),
+            };
+            if status_code == 200 {
+                info!("Serving for {}", &url);
+                debug!("Serving DID Doc: {:?}", result);
+            } else {
+                error!("{}: {}", status_code, result);
             }
+            Response::from_data(content_type, result).with_status_code(status_code)
         } else {
             info!("400: unrecognized path: {}", &url);
             Response::text("400").with_status_code(400)
@@ -162,7 +254,19 @@ fn init_resolvers(args: Args) -> Resolvers {
                     pool
                 };
 
-                resolvers.insert(ledger_prefix, Resolver::new(pool));
+                let mut resolver = Resolver::new(pool)
+                    .require_state_proof_present(args.require_state_proof_present);
+                if args.cache_size > 0 {
+                    resolver =
+                        resolver.enable_cache(args.cache_size, Duration::from_secs(args.cache_ttl));
+                }
+                if args.nym_version_metadata {
+                    resolver = resolver.enable_nym_version_metadata();
+                }
+                if args.endorser_metadata {
+                    resolver = resolver.enable_endorser_metadata();
+                }
+                resolvers.insert(ledger_prefix, resolver);
             }
         }
     }
@@ -171,18 +275,326 @@ fn init_resolvers(args: Args) -> Resolvers {
     resolvers
 }
 
-fn process_request(request: &str, resolvers: &Resolvers) -> DidIndyResult<String> {
+// Returns the HTTP status code, the content type and the serialized resolution or
+// dereferencing result
+fn process_request(
+    request: &str,
+    accept: Option<&str>,
+    transform_keys: Option<&str>,
+    resolvers: &Resolvers,
+) -> (u16, &'static str, String) {
+    let is_dereferencing = DidUrl::from_str(request)
+        .map(|did| {
+            did.path.is_some()
+                || did.fragment.is_some()
+                || did.query.contains_key(&QueryParameter::Service)
+        })
+        .unwrap_or(false);
+
+    let keys = transform_keys.map_or(Ok(Default::default()), str::parse);
+    let (error_code, deactivated, result) = if is_dereferencing {
+        let result = check_accept(accept)
+            .and_then(resolution_result_only)
+            .and(keys)
+            .and_then(|keys| {
+                get_resolver(request, resolvers)
+                    .and_then(|resolver| resolver.dereference_with_keys_blocking(request, keys))
+            })
+            .unwrap_or_else(|err| DereferencingResult::from_error(&err));
+        (
+            result.dereferencing_metadata().error().map(String::from),
+            false,
+            serde_json::to_string_pretty(&result),
+        )
+    } else {
+        let (representation, result) = match check_accept(accept) {
+            Ok(representation) => (
+                representation,
+                keys.and_then(|keys| {
+                    get_resolver(request, resolvers)
+                        .and_then(|resolver| resolver.resolve_with_keys_blocking(request, keys))
+                }),
+            ),
+            Err(err) => (Representation::ResolutionResult, Err(err)),
+        };
+        let result = result.unwrap_or_else(|err| ResolutionResult::from_error(&err));
+        let deactivated = result
+            .did_document_metadata()
+            .map(|metadata| metadata.deactivated())
+            .unwrap_or(false);
+        let error_code = result.did_resolution_metadata().error().map(String::from);
+        // Clients asking for a DID document media type get the bare DID document
+        match (representation, result.did_document()) {
+            (Representation::DidDocument(media_type), Some(did_document))
+                if error_code.is_none() =>
+            {
+                let did_document = serde_json::to_string_pretty(did_document);
+                return match did_document {
+                    Ok(did_document) if deactivated => (410, media_type, did_document),
+                    Ok(did_document) => (200, media_type, did_document),
+                    Err(err) => (500, RESOLUTION_RESULT_CONTENT_TYPE, err.to_string()),
+                };
+            }
+            _ => (
+                error_code,
+                deactivated,
+                serde_json::to_string_pretty(&result),
+            ),
+        }
+    };
+
+    let content_type = RESOLUTION_RESULT_CONTENT_TYPE;
+    match result {
+        // Deactivated DIDs are answered with 410 Gone by the DID Resolution HTTP(S) binding
+        Ok(result) if deactivated => (410, content_type, result),
+        Ok(result) => (status_code(error_code.as_deref()), content_type, result),
+        Err(err) => (500, content_type, err.to_string()),
+    }
+}
+
+fn process_legacy_request(
+    legacy_id: &str,
+    namespace: &str,
+    accept: Option<&str>,
+    resolvers: &Resolvers,
+) -> (u16, String) {
+    let result = check_accept(accept)
+        .and_then(resolution_result_only)
+        .and_then(|_| {
+            resolvers
+                .get(namespace)
+                .ok_or(DidIndyError::NamespaceNotSupported)
+        })
+        .and_then(|resolver| resolver.dereference_legacy_blocking(namespace, legacy_id))
+        .unwrap_or_else(|err| DereferencingResult::from_error(&err));
+
+    let error_code = result.dereferencing_metadata().error().map(String::from);
+    match serde_json::to_string_pretty(&result) {
+        Ok(result) => (status_code(error_code.as_deref()), result),
+        Err(err) => (500, err.to_string()),
+    }
+}
+
+// Returns all versions of a DID document, failures are reported as resolution result
+fn process_history_request(did: &str, resolvers: &Resolvers) -> (u16, String) {
+    let history =
+        get_resolver(did, resolvers).and_then(|resolver| resolver.resolve_history_blocking(did));
+    let result = match history {
+        Ok(history) => serde_json::to_string_pretty(&history).map(|history| (200, history)),
+        Err(err) => serde_json::to_string_pretty(&ResolutionResult::from_error(&err))
+            .map(|result| (status_code(Some(err.error_code())), result)),
+    };
+    result.unwrap_or_else(|err| (500, err.to_string()))
+}
+
+// Dereferences a JSON array of DID URLs concurrently, the results keep the order of the request
+fn process_batch_request(body: &str, accept: Option<&str>, resolvers: &Resolvers) -> (u16, String) {
+    if let Err(err) = check_accept(accept).and_then(resolution_result_only) {
+        return match serde_json::to_string_pretty(&DereferencingResult::from_error(&err)) {
+            Ok(result) => (status_code(Some(err.error_code())), result),
+            Err(err) => (500, err.to_string()),
+        };
+    }
+    let did_urls: Vec<String> = match serde_json::from_str(body) {
+        Ok(did_urls) => did_urls,
+        Err(err) => return (400, err.to_string()),
+    };
+    if did_urls.len() > MAX_BATCH_SIZE {
+        return (
+            413,
+            format!("batch exceeds the maximum of {} DID URLs", MAX_BATCH_SIZE),
+        );
+    }
+
+    let results = block_on(join_all(
+        did_urls
+            .iter()
+            .map(|did_url| dereference(did_url, resolvers)),
+    ));
+
+    match serde_json::to_string_pretty(&results) {
+        Ok(results) => (200, results),
+        Err(err) => (500, err.to_string()),
+    }
+}
+
+// Reads the JSON body of a batch request, sent as application/json or text/plain
+fn read_batch_body<R: Read>(content_type: Option<&str>, data: R) -> Result<String, (u16, String)> {
+    let media_type = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(str::trim);
+    if !matches!(media_type, Some(media_type) if BATCH_MEDIA_TYPES.contains(&media_type)) {
+        return Err((415, String::from("batch must be sent as application/json")));
+    }
+
+    let mut body = String::new();
+    data.take(MAX_BATCH_BODY_SIZE + 1)
+        .read_to_string(&mut body)
+        .map_err(|err| (400, err.to_string()))?;
+    if body.len() as u64 > MAX_BATCH_BODY_SIZE {
+        return Err((
+            413,
+            format!("batch exceeds the maximum of {} bytes", MAX_BATCH_BODY_SIZE),
+        ));
+    }
+    Ok(body)
+}
+
+async fn dereference(did_url: &str, resolvers: &Resolvers) -> DereferencingResult {
+    let result = match get_resolver(did_url, resolvers) {
+        Ok(resolver) => resolver.dereference(did_url).await,
+        Err(err) => Err(err),
+    };
+    result.unwrap_or_else(|err| DereferencingResult::from_error(&err))
+}
+
+fn get_resolver<'a>(
+    request: &str,
+    resolvers: &'a Resolvers,
+) -> DidIndyResult<&'a Resolver<SharedPool>> {
     let did = DidUrl::from_str(request)?;
-    let resolver = if let Some(resolver) = resolvers.get(&did.namespace) {
-        resolver
+    if let Some(resolver) = resolvers.get(&did.namespace) {
+        Ok(resolver)
     } else {
         error!("Requested Indy Namespace \"{}\" unknown", &did.namespace);
-        return Err(DidIndyError::NamespaceNotSupported);
+        Err(DidIndyError::NamespaceNotSupported)
+    }
+}
+
+// Picks the first supported media type of the Accept header, media type parameters and
+// quality values are ignored
+fn check_accept(accept: Option<&str>) -> DidIndyResult<Representation> {
+    let accept = match accept {
+        Some(accept) if !accept.trim().is_empty() => accept,
+        _ => return Ok(Representation::ResolutionResult),
     };
+    accept
+        .split(',')
+        .map(|media_type| media_type.split(';').next().unwrap_or("").trim())
+        .find_map(|media_type| {
+            if SUPPORTED_MEDIA_TYPES.contains(&media_type) {
+                Some(Representation::ResolutionResult)
+            } else {
+                DID_DOCUMENT_MEDIA_TYPES
+                    .iter()
+                    .find(|supported| **supported == media_type)
+                    .map(|supported| Representation::DidDocument(supported))
+            }
+        })
+        .ok_or(DidIndyError::RepresentationNotSupported)
+}
 
-    if did.path.is_none() {
-        resolver.resolve(request)
-    } else {
-        resolver.dereference(request)
+// Only resolving a DID returns a bare DID document, all other requests return a result
+fn resolution_result_only(representation: Representation) -> DidIndyResult<()> {
+    match representation {
+        Representation::ResolutionResult => Ok(()),
+        Representation::DidDocument(_) => Err(DidIndyError::RepresentationNotSupported),
+    }
+}
+
+// HTTP status codes of the DID Resolution HTTP(S) binding
+fn status_code(error_code: Option<&str>) -> u16 {
+    match error_code {
+        None => 200,
+        Some(INVALID_DID) => 400,
+        Some(NOT_FOUND) => 404,
+        Some(REPRESENTATION_NOT_SUPPORTED) => 406,
+        Some(METHOD_NOT_SUPPORTED) => 501,
+        Some(INTERNAL_ERROR) | Some(_) => 500,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_accept_of_resolution_result() {
+        for accept in [
+            None,
+            Some(""),
+            Some("*/*"),
+            Some("application/json"),
+            Some("text/html, application/ld+json;profile=\"https://w3id.org/did-resolution\""),
+        ] {
+            assert_eq!(
+                check_accept(accept).unwrap(),
+                Representation::ResolutionResult
+            );
+        }
+    }
+
+    #[test]
+    fn check_accept_of_did_document() {
+        assert_eq!(
+            check_accept(Some("application/did+json")).unwrap(),
+            Representation::DidDocument("application/did+json")
+        );
+        assert_eq!(
+            check_accept(Some("text/html, application/did+ld+json;q=0.9, */*;q=0.1")).unwrap(),
+            Representation::DidDocument("application/did+ld+json")
+        );
+    }
+
+    #[test]
+    fn check_accept_of_unsupported_media_type() {
+        assert!(matches!(
+            check_accept(Some("text/html, application/xml")),
+            Err(DidIndyError::RepresentationNotSupported)
+        ));
+    }
+
+    #[test]
+    fn resolution_result_only_rejects_did_document() {
+        assert!(resolution_result_only(Representation::ResolutionResult).is_ok());
+        assert!(matches!(
+            resolution_result_only(Representation::DidDocument("application/did+json")),
+            Err(DidIndyError::RepresentationNotSupported)
+        ));
+    }
+
+    #[test]
+    fn read_batch_body_of_json() {
+        let body = r#"["did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp"]"#;
+        for content_type in [
+            "application/json",
+            "application/json; charset=utf-8",
+            "text/plain",
+        ] {
+            assert_eq!(
+                read_batch_body(Some(content_type), body.as_bytes()).unwrap(),
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn read_batch_body_rejects_other_content_types() {
+        for content_type in [None, Some("application/x-www-form-urlencoded")] {
+            assert!(matches!(
+                read_batch_body(content_type, "[]".as_bytes()),
+                Err((415, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn read_batch_body_rejects_oversized_body() {
+        let body = vec![b' '; MAX_BATCH_BODY_SIZE as usize + 1];
+        assert!(matches!(
+            read_batch_body(Some("application/json"), body.as_slice()),
+            Err((413, _))
+        ));
+    }
+
+    #[test]
+    fn status_codes_of_error_codes() {
+        assert_eq!(status_code(None), 200);
+        assert_eq!(status_code(Some(INVALID_DID)), 400);
+        assert_eq!(status_code(Some(NOT_FOUND)), 404);
+        assert_eq!(status_code(Some(REPRESENTATION_NOT_SUPPORTED)), 406);
+        assert_eq!(status_code(Some(METHOD_NOT_SUPPORTED)), 501);
+        assert_eq!(status_code(Some(INTERNAL_ERROR)), 500);
+        assert_eq!(status_code(Some("unknownError")), 500);
     }
 }
@@ -0,0 +1,423 @@
+use indy_vdr::ledger::identifiers::CredentialDefinitionId;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::conversion;
+pub use super::conversion::{cred_def_did_url, rev_reg_def_did_url, schema_did_url};
+use super::error::{DidIndyError, DidIndyResult};
+use super::responses::GetSchemaResult;
+
+/// Schema as defined by the AnonCreds specification.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    pub issuer_id: String,
+    pub name: String,
+    pub version: String,
+    pub attr_names: Vec<String>,
+}
+
+impl Schema {
+    pub fn from_ledger(issuer_id: &str, schema: GetSchemaResult) -> Self {
+        Schema {
+            issuer_id: issuer_id.to_string(),
+            name: schema.name,
+            version: schema.version,
+            attr_names: schema.attr_names,
+        }
+    }
+}
+
+/// Credential definition as defined by the AnonCreds specification.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialDefinition {
+    pub issuer_id: String,
+    pub schema_id: String,
+    #[serde(rename = "type")]
+    pub signature_type: String,
+    pub tag: String,
+    pub value: Value,
+}
+
+impl CredentialDefinition {
+    /// Builds the credential definition from the `result` of a GET_CRED_DEF reply.
+    pub fn from_ledger(issuer_id: &str, schema_id: &str, result: &Value) -> DidIndyResult<Self> {
+        let field = |name: &str| {
+            result[name]
+                .as_str()
+                .map(String::from)
+                .ok_or(DidIndyError::UnexpectedLedgerData)
+        };
+        if result["data"].is_null() {
+            return Err(DidIndyError::EmptyData);
+        }
+        Ok(CredentialDefinition {
+            issuer_id: issuer_id.to_string(),
+            schema_id: schema_id.to_string(),
+            signature_type: field("signature_type")?,
+            tag: field("tag")?,
+            value: result["data"].to_owned(),
+        })
+    }
+}
+
+/// Revocation registry definition as defined by the AnonCreds specification.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RevocationRegistryDefinition {
+    pub issuer_id: String,
+    pub revoc_def_type: String,
+    pub cred_def_id: String,
+    pub tag: String,
+    pub value: RevocationRegistryDefinitionValue,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RevocationRegistryDefinitionValue {
+    pub public_keys: RevocationRegistryPublicKeys,
+    pub max_cred_num: u32,
+    pub tails_location: String,
+    pub tails_hash: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RevocationRegistryPublicKeys {
+    pub accum_key: Value,
+}
+
+// Revocation registry definition as written to the ledger
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LedgerRevocationRegistryDefinition {
+    revoc_def_type: String,
+    cred_def_id: String,
+    tag: String,
+    value: RevocationRegistryDefinitionValue,
+}
+
+impl RevocationRegistryDefinition {
+    /// Builds the registry definition from the `data` of a GET_REVOC_REG_DEF reply,
+    /// the legacy credential definition id is converted to a did:indy DID URL.
+    pub fn from_ledger(namespace: &str, issuer_id: &str, data: Value) -> DidIndyResult<Self> {
+        let rev_reg_def: LedgerRevocationRegistryDefinition = serde_json::from_value(data)?;
+        Ok(RevocationRegistryDefinition {
+            issuer_id: issuer_id.to_string(),
+            revoc_def_type: rev_reg_def.revoc_def_type,
+            cred_def_id: conversion::cred_def_id_to_did_url(
+                namespace,
+                &CredentialDefinitionId::from(rev_reg_def.cred_def_id),
+            )
+            .map_err(|_| DidIndyError::UnexpectedLedgerData)?,
+            tag: rev_reg_def.tag,
+            value: rev_reg_def.value,
+        })
+    }
+}
+
+/// Revocation status list as defined by the AnonCreds specification.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RevocationStatusList {
+    pub issuer_id: String,
+    pub rev_reg_def_id: String,
+    /// One bit per credential index, `1` marks a revoked credential.
+    pub revocation_list: Vec<u8>,
+    pub current_accumulator: String,
+    pub timestamp: i64,
+}
+
+impl RevocationStatusList {
+    /// Builds the status list from the `data` of a GET_REVOC_REG_DEF reply and the `data`
+    /// of a GET_REVOC_REG_DELTA reply starting at the creation of the registry.
+    pub fn from_ledger(
+        issuer_id: &str,
+        rev_reg_def_id: &str,
+        rev_reg_def: &Value,
+        delta: &Value,
+    ) -> DidIndyResult<Self> {
+        let max_cred_num = rev_reg_def["value"]["maxCredNum"]
+            .as_u64()
+            .ok_or(DidIndyError::UnexpectedLedgerData)? as usize;
+        // Credentials issued on demand are treated as revoked until they are issued
+        let initial = match rev_reg_def["value"]["issuanceType"].as_str() {
+            Some("ISSUANCE_ON_DEMAND") => 1,
+            _ => 0,
+        };
+        let mut revocation_list = vec![initial; max_cred_num];
+
+        let value = &delta["value"];
+        for (key, bit) in [("issued", 0), ("revoked", 1)] {
+            for index in value[key].as_array().into_iter().flatten() {
+                // Indy credential indices start at 1
+                let index = index
+                    .as_u64()
+                    .filter(|index| *index >= 1 && *index as usize <= max_cred_num)
+                    .ok_or(DidIndyError::UnexpectedLedgerData)?;
+                revocation_list[index as usize - 1] = bit;
+            }
+        }
+
+        let accum_to = &value["accum_to"];
+        Ok(RevocationStatusList {
+            issuer_id: issuer_id.to_string(),
+            rev_reg_def_id: rev_reg_def_id.to_string(),
+            revocation_list,
+            current_accumulator: accum_to["value"]["accum"]
+                .as_str()
+                .map(String::from)
+                .ok_or(DidIndyError::UnexpectedLedgerData)?,
+            timestamp: accum_to["txnTime"]
+                .as_i64()
+                .ok_or(DidIndyError::UnexpectedLedgerData)?,
+        })
+    }
+}
+
+/// Converts a legacy Indy identifier of a schema (`<did>:2:<name>:<version>`), credential
+/// definition (`<did>:3:CL:<seqNo>:<tag>`) or revocation registry
+/// (`<did>:4:<cred def id>:CL_ACCUM:<tag>`) to the did:indy DID URL in `namespace`.
+pub fn legacy_id_to_did_url(namespace: &str, legacy_id: &str) -> DidIndyResult<String> {
+    conversion::to_did_url(namespace, legacy_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn schema_from_ledger() {
+        let data = json!({
+            "attr_names": ["name", "age"],
+            "name": "npdb",
+            "version": "4.3.4"
+        });
+        let schema = Schema::from_ledger(
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+            serde_json::from_value(data).unwrap(),
+        );
+
+        let expected = json!({
+            "issuerId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+            "name": "npdb",
+            "version": "4.3.4",
+            "attrNames": ["name", "age"]
+        });
+        assert_eq!(serde_json::to_value(&schema).unwrap(), expected);
+
+        let deserialized: Schema = serde_json::from_value::<Value>(expected)
+            .and_then(serde_json::from_value)
+            .unwrap();
+        assert_eq!(deserialized, schema);
+    }
+
+    #[test]
+    fn cred_def_from_ledger() {
+        let result = json!({
+            "identifier": "Dk1fRRTtNazyMuK2cr64wp",
+            "origin": "Dk1fRRTtNazyMuK2cr64wp",
+            "ref": 10,
+            "signature_type": "CL",
+            "tag": "default",
+            "type": "108",
+            "data": {"primary": {"n": "779", "s": "750"}}
+        });
+        let schema_id = schema_did_url("idunion", "Dk1fRRTtNazyMuK2cr64wp", "npdb", "4.3.4");
+        let cred_def = CredentialDefinition::from_ledger(
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+            &schema_id,
+            &result,
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&cred_def).unwrap(),
+            json!({
+                "issuerId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+                "schemaId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4",
+                "type": "CL",
+                "tag": "default",
+                "value": {"primary": {"n": "779", "s": "750"}}
+            })
+        );
+    }
+
+    #[test]
+    fn cred_def_without_data_is_empty() {
+        let result = json!({"ref": 10, "signature_type": "CL", "tag": "default", "data": null});
+        assert!(matches!(
+            CredentialDefinition::from_ledger("issuer", "schema", &result),
+            Err(DidIndyError::EmptyData)
+        ));
+    }
+
+    #[test]
+    fn rev_reg_def_from_ledger() {
+        let data = json!({
+            "ver": "1.0",
+            "id": "Dk1fRRTtNazyMuK2cr64wp:4:Dk1fRRTtNazyMuK2cr64wp:3:CL:10:default:CL_ACCUM:tag",
+            "revocDefType": "CL_ACCUM",
+            "tag": "tag",
+            "credDefId": "Dk1fRRTtNazyMuK2cr64wp:3:CL:10:default",
+            "value": {
+                "issuanceType": "ISSUANCE_BY_DEFAULT",
+                "maxCredNum": 100,
+                "publicKeys": {"accumKey": {"z": "1 0BB"}},
+                "tailsHash": "9Ctq2dhgTHmTGgTnTfm1rr8zGykPJrn4hvB5XGGgtbW7",
+                "tailsLocation": "https://tails.example.com/9Ctq2dhg"
+            }
+        });
+        let rev_reg_def = RevocationRegistryDefinition::from_ledger(
+            "idunion",
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+            data,
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&rev_reg_def).unwrap(),
+            json!({
+                "issuerId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+                "revocDefType": "CL_ACCUM",
+                "credDefId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/CLAIM_DEF/10/default",
+                "tag": "tag",
+                "value": {
+                    "publicKeys": {"accumKey": {"z": "1 0BB"}},
+                    "maxCredNum": 100,
+                    "tailsLocation": "https://tails.example.com/9Ctq2dhg",
+                    "tailsHash": "9Ctq2dhgTHmTGgTnTfm1rr8zGykPJrn4hvB5XGGgtbW7"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn rev_reg_def_with_invalid_cred_def_id() {
+        let data = json!({
+            "revocDefType": "CL_ACCUM",
+            "tag": "tag",
+            "credDefId": "Dk1fRRTtNazyMuK2cr64wp:2:npdb:4.3.4",
+            "value": {
+                "maxCredNum": 100,
+                "publicKeys": {"accumKey": {"z": "1 0BB"}},
+                "tailsHash": "9Ctq2dhg",
+                "tailsLocation": "https://tails.example.com/9Ctq2dhg"
+            }
+        });
+        assert!(matches!(
+            RevocationRegistryDefinition::from_ledger("idunion", "issuer", data),
+            Err(DidIndyError::UnexpectedLedgerData)
+        ));
+    }
+
+    fn rev_reg_def(issuance_type: &str) -> Value {
+        json!({
+            "revocDefType": "CL_ACCUM",
+            "tag": "tag",
+            "credDefId": "Dk1fRRTtNazyMuK2cr64wp:3:CL:10:default",
+            "value": {
+                "issuanceType": issuance_type,
+                "maxCredNum": 5,
+                "publicKeys": {"accumKey": {"z": "1 0BB"}},
+                "tailsHash": "9Ctq2dhg",
+                "tailsLocation": "https://tails.example.com/9Ctq2dhg"
+            }
+        })
+    }
+
+    fn delta(issued: Value, revoked: Value) -> Value {
+        json!({
+            "revocDefType": "CL_ACCUM",
+            "value": {
+                "accum_to": {"txnTime": 1639497660, "value": {"accum": "21 11EA"}},
+                "issued": issued,
+                "revoked": revoked
+            }
+        })
+    }
+
+    #[test]
+    fn status_list_issued_by_default() {
+        let status_list = RevocationStatusList::from_ledger(
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/10/default/tag",
+            &rev_reg_def("ISSUANCE_BY_DEFAULT"),
+            &delta(json!([]), json!([2, 5])),
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&status_list).unwrap(),
+            json!({
+                "issuerId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp",
+                "revRegDefId": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/10/default/tag",
+                "revocationList": [0, 1, 0, 0, 1],
+                "currentAccumulator": "21 11EA",
+                "timestamp": 1639497660
+            })
+        );
+    }
+
+    #[test]
+    fn status_list_issued_on_demand() {
+        let status_list = RevocationStatusList::from_ledger(
+            "issuer",
+            "rev_reg_def",
+            &rev_reg_def("ISSUANCE_ON_DEMAND"),
+            &delta(json!([1, 2, 3]), json!([2])),
+        )
+        .unwrap();
+
+        assert_eq!(status_list.revocation_list, vec![0, 1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn status_list_with_index_out_of_range() {
+        assert!(matches!(
+            RevocationStatusList::from_ledger(
+                "issuer",
+                "rev_reg_def",
+                &rev_reg_def("ISSUANCE_BY_DEFAULT"),
+                &delta(json!([]), json!([6])),
+            ),
+            Err(DidIndyError::UnexpectedLedgerData)
+        ));
+    }
+
+    #[test]
+    fn legacy_ids_to_did_urls() {
+        assert_eq!(
+            legacy_id_to_did_url("idunion", "Dk1fRRTtNazyMuK2cr64wp:2:npdb:4.3.4").unwrap(),
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4"
+        );
+        assert_eq!(
+            legacy_id_to_did_url("idunion", "Dk1fRRTtNazyMuK2cr64wp:3:CL:104:default").unwrap(),
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/CLAIM_DEF/104/default"
+        );
+        assert_eq!(
+            legacy_id_to_did_url(
+                "idunion",
+                "Dk1fRRTtNazyMuK2cr64wp:4:Dk1fRRTtNazyMuK2cr64wp:3:CL:104:revocable:CL_ACCUM:a4e25e54"
+            )
+            .unwrap(),
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54"
+        );
+    }
+
+    #[test]
+    fn invalid_legacy_ids() {
+        for legacy_id in [
+            "Dk1fRRTtNazyMuK2cr64wp",
+            "Dk1fRRTtNazyMuK2cr64wp:1:npdb:4.3.4",
+            "Dk1fRRTtNazyMuK2cr64wp:3:CL:npdb:default",
+        ] {
+            assert!(matches!(
+                legacy_id_to_did_url("idunion", legacy_id),
+                Err(DidIndyError::InvalidDidUrl)
+            ));
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Expiry {
+    Never,
+    After(Duration),
+}
+
+struct Entries<V> {
+    values: HashMap<String, (V, Option<Instant>)>,
+    // Keys in insertion order, the oldest entry is evicted first
+    order: VecDeque<String>,
+}
+
+pub struct Cache<V: Clone> {
+    capacity: usize,
+    entries: Mutex<Entries<V>>,
+}
+
+impl<V: Clone> Cache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Cache {
+            capacity,
+            entries: Mutex::new(Entries {
+                values: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut entries = self.entries.lock().ok()?;
+        let expired = match entries.values.get(key) {
+            Some((_, Some(expires_at))) => *expires_at <= Instant::now(),
+            Some((_, None)) => false,
+            None => return None,
+        };
+        if expired {
+            entries.values.remove(key);
+            entries.order.retain(|k| k != key);
+            None
+        } else {
+            entries.values.get(key).map(|(value, _)| value.clone())
+        }
+    }
+
+    pub fn insert(&self, key: &str, value: V, expiry: Expiry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        if entries.values.contains_key(key) {
+            entries.order.retain(|k| k != key);
+        } else {
+            while entries.values.len() >= self.capacity {
+                match entries.order.pop_front() {
+                    Some(oldest) => entries.values.remove(&oldest),
+                    None => break,
+                };
+            }
+        }
+
+        let expires_at = match expiry {
+            Expiry::Never => None,
+            Expiry::After(ttl) => Some(Instant::now() + ttl),
+        };
+        entries.values.insert(key.to_string(), (value, expires_at));
+        entries.order.push_back(key.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_inserted_value() {
+        let cache = Cache::new(2);
+        cache.insert("a", 1, Expiry::Never);
+
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("b"), None);
+    }
+
+    #[test]
+    fn expired_value_is_removed() {
+        let cache = Cache::new(2);
+        cache.insert("a", 1, Expiry::After(Duration::from_secs(0)));
+
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn oldest_value_is_evicted() {
+        let cache = Cache::new(2);
+        cache.insert("a", 1, Expiry::Never);
+        cache.insert("b", 2, Expiry::Never);
+        cache.insert("c", 3, Expiry::Never);
+
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(2));
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn reinserted_value_is_not_evicted() {
+        let cache = Cache::new(2);
+        cache.insert("a", 1, Expiry::Never);
+        cache.insert("b", 2, Expiry::Never);
+        cache.insert("a", 3, Expiry::Never);
+        cache.insert("c", 4, Expiry::Never);
+
+        assert_eq!(cache.get("a"), Some(3));
+        assert_eq!(cache.get("b"), None);
+    }
+
+    #[test]
+    fn cache_without_capacity_stores_nothing() {
+        let cache = Cache::new(0);
+        cache.insert("a", 1, Expiry::Never);
+
+        assert_eq!(cache.get("a"), None);
+    }
+}
@@ -0,0 +1,257 @@
+use indy_vdr::ledger::identifiers::{CredentialDefinitionId, RevocationRegistryId, SchemaId};
+use indy_vdr::utils::did::DidValue;
+use indy_vdr::utils::validation::Validatable;
+
+use std::fmt;
+use urlencoding::encode;
+
+use super::did::{ClaimDef, DidUrl, LedgerObject, RevReg, Schema};
+use super::error::{DidIndyError, DidIndyResult};
+
+// Signature and registry types supported by the anoncreds v0 object family
+static SIGNATURE_TYPE: &str = "CL";
+static REVOCATION_REGISTRY_TYPE: &str = "CL_ACCUM";
+
+/// Legacy Indy identifier of an AnonCreds object.
+#[derive(Debug, PartialEq)]
+pub enum LegacyId {
+    Schema(SchemaId),
+    CredentialDefinition(CredentialDefinitionId),
+    RevocationRegistry(RevocationRegistryId),
+}
+
+impl fmt::Display for LegacyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LegacyId::Schema(id) => f.write_str(id),
+            LegacyId::CredentialDefinition(id) => f.write_str(id),
+            LegacyId::RevocationRegistry(id) => f.write_str(id),
+        }
+    }
+}
+
+/// Returns the legacy identifier of the ledger object a did:indy DID URL points to.
+pub fn to_legacy_id(did_url: &DidUrl) -> DidIndyResult<LegacyId> {
+    let path = did_url.path.as_ref().ok_or(DidIndyError::InvalidDidUrl)?;
+    let legacy_id = match LedgerObject::from_str(path)? {
+        LedgerObject::Schema(schema) => LegacyId::Schema(schema_id(&did_url.id, &schema)),
+        LedgerObject::ClaimDef(claim_def) => {
+            LegacyId::CredentialDefinition(cred_def_id(&did_url.id, &claim_def))
+        }
+        LedgerObject::RevRegDef(rev_reg)
+        | LedgerObject::RevRegEntry(rev_reg)
+        | LedgerObject::RevRegDelta(rev_reg) => {
+            LegacyId::RevocationRegistry(rev_reg_id(&did_url.id, &rev_reg))
+        }
+        LedgerObject::Txn(_) => return Err(DidIndyError::InvalidDidUrl),
+    };
+    Ok(legacy_id)
+}
+
+/// `<did>:2:<name>:<version>`
+pub fn schema_id(did: &DidValue, schema: &Schema) -> SchemaId {
+    SchemaId::new(did, &schema.name, &schema.version)
+}
+
+/// `<did>:3:CL:<schema seqNo>:<tag>`
+pub fn cred_def_id(did: &DidValue, claim_def: &ClaimDef) -> CredentialDefinitionId {
+    CredentialDefinitionId::new(
+        did,
+        &SchemaId::from(claim_def.schema_seq_no.to_string()),
+        SIGNATURE_TYPE,
+        &claim_def.name,
+    )
+}
+
+/// `<did>:4:<cred def id>:CL_ACCUM:<tag>`, the credential definition is issued by the same DID.
+pub fn rev_reg_id(did: &DidValue, rev_reg: &RevReg) -> RevocationRegistryId {
+    let claim_def = ClaimDef {
+        schema_seq_no: rev_reg.schema_seq_no,
+        name: rev_reg.claim_def_name.clone(),
+    };
+    RevocationRegistryId::new(
+        did,
+        &cred_def_id(did, &claim_def),
+        REVOCATION_REGISTRY_TYPE,
+        &rev_reg.tag,
+    )
+}
+
+/// Converts a legacy identifier of a schema, credential definition or revocation registry
+/// to the did:indy DID URL in `namespace`.
+pub fn to_did_url(namespace: &str, legacy_id: &str) -> DidIndyResult<String> {
+    match legacy_id.split(':').nth(1) {
+        Some("2") => schema_id_to_did_url(namespace, &SchemaId::from(legacy_id.to_string())),
+        Some("3") => cred_def_id_to_did_url(
+            namespace,
+            &CredentialDefinitionId::from(legacy_id.to_string()),
+        ),
+        Some("4") => rev_reg_id_to_did_url(
+            namespace,
+            &RevocationRegistryId::from(legacy_id.to_string()),
+        ),
+        _ => Err(DidIndyError::InvalidDidUrl),
+    }
+}
+
+pub fn schema_id_to_did_url(namespace: &str, id: &SchemaId) -> DidIndyResult<String> {
+    let (issuer, name, version) = validated(id)?.parts().ok_or(DidIndyError::InvalidDidUrl)?;
+    Ok(schema_did_url(namespace, &issuer, &name, &version))
+}
+
+pub fn cred_def_id_to_did_url(
+    namespace: &str,
+    id: &CredentialDefinitionId,
+) -> DidIndyResult<String> {
+    let (issuer, schema_seq_no, tag) = cred_def_parts(id)?;
+    Ok(cred_def_did_url(namespace, &issuer, schema_seq_no, &tag))
+}
+
+pub fn rev_reg_id_to_did_url(namespace: &str, id: &RevocationRegistryId) -> DidIndyResult<String> {
+    let (issuer, cred_def_id, rev_reg_type, tag) =
+        validated(id)?.parts().ok_or(DidIndyError::InvalidDidUrl)?;
+    if rev_reg_type != REVOCATION_REGISTRY_TYPE {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+    // The DID URL addresses the credential definition by the issuer of the registry
+    let (cred_def_issuer, schema_seq_no, cred_def_tag) = cred_def_parts(&cred_def_id)?;
+    if cred_def_issuer != issuer {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+    Ok(rev_reg_def_did_url(
+        namespace,
+        &issuer,
+        schema_seq_no,
+        &cred_def_tag,
+        &tag,
+    ))
+}
+
+// did:indy only addresses CL credential definitions by the seqNo of their schema
+fn cred_def_parts(id: &CredentialDefinitionId) -> DidIndyResult<(DidValue, u32, String)> {
+    let (issuer, signature_type, schema_id, tag) =
+        validated(id)?.parts().ok_or(DidIndyError::InvalidDidUrl)?;
+    let schema_seq_no = schema_id
+        .parse::<u32>()
+        .map_err(|_| DidIndyError::InvalidDidUrl)?;
+    if signature_type != SIGNATURE_TYPE {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+    Ok((issuer, schema_seq_no, tag))
+}
+
+fn validated<T: Validatable>(id: &T) -> DidIndyResult<&T> {
+    id.validate().map_err(|_| DidIndyError::InvalidDidUrl)?;
+    Ok(id)
+}
+
+/// did:indy DID URL of a schema, name and version are percent-encoded.
+pub fn schema_did_url(namespace: &str, issuer: &str, name: &str, version: &str) -> String {
+    format!(
+        "did:indy:{}:{}/anoncreds/v0/SCHEMA/{}/{}",
+        namespace,
+        issuer,
+        encode(name),
+        encode(version)
+    )
+}
+
+/// did:indy DID URL of a credential definition, the tag is percent-encoded.
+pub fn cred_def_did_url(namespace: &str, issuer: &str, schema_seq_no: u32, tag: &str) -> String {
+    format!(
+        "did:indy:{}:{}/anoncreds/v0/CLAIM_DEF/{}/{}",
+        namespace,
+        issuer,
+        schema_seq_no,
+        encode(tag)
+    )
+}
+
+/// did:indy DID URL of a revocation registry definition, the tags are percent-encoded.
+pub fn rev_reg_def_did_url(
+    namespace: &str,
+    issuer: &str,
+    schema_seq_no: u32,
+    cred_def_tag: &str,
+    tag: &str,
+) -> String {
+    format!(
+        "did:indy:{}:{}/anoncreds/v0/REV_REG_DEF/{}/{}/{}",
+        namespace,
+        issuer,
+        schema_seq_no,
+        encode(cred_def_tag),
+        encode(tag)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4",
+        "Dk1fRRTtNazyMuK2cr64wp:2:npdb:4.3.4"
+    )]
+    #[case(
+        "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/CLAIM_DEF/104/default",
+        "Dk1fRRTtNazyMuK2cr64wp:3:CL:104:default"
+    )]
+    #[case(
+        "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54",
+        "Dk1fRRTtNazyMuK2cr64wp:4:Dk1fRRTtNazyMuK2cr64wp:3:CL:104:revocable:CL_ACCUM:a4e25e54"
+    )]
+    #[case(
+        "did:indy:sovrin:staging:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4",
+        "Dk1fRRTtNazyMuK2cr64wp:2:npdb:4.3.4"
+    )]
+    #[case(
+        "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/My%20Schema/1.0",
+        "Dk1fRRTtNazyMuK2cr64wp:2:My Schema:1.0"
+    )]
+    #[case(
+        "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/CLAIM_DEF/104/my%20tag",
+        "Dk1fRRTtNazyMuK2cr64wp:3:CL:104:my tag"
+    )]
+    fn round_trip(#[case] did_url: &str, #[case] legacy_id: &str) {
+        let parsed = DidUrl::from_str(did_url).unwrap();
+        assert_eq!(to_legacy_id(&parsed).unwrap().to_string(), legacy_id);
+        assert_eq!(to_did_url(&parsed.namespace, legacy_id).unwrap(), did_url);
+    }
+
+    #[test]
+    fn rev_reg_entries_map_to_registry_id() {
+        let did_url = DidUrl::from_str(
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54",
+        )
+        .unwrap();
+        assert!(matches!(
+            to_legacy_id(&did_url).unwrap(),
+            LegacyId::RevocationRegistry(_)
+        ));
+    }
+
+    #[test]
+    fn did_without_path_has_no_legacy_id() {
+        let did_url = DidUrl::from_str("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp").unwrap();
+        assert!(matches!(
+            to_legacy_id(&did_url),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+    }
+
+    #[rstest]
+    #[case("Dk1fRRTtNazyMuK2cr64wp")]
+    #[case("Dk1fRRTtNazyMuK2cr64wp:1:npdb:4.3.4")]
+    #[case("Dk1fRRTtNazyMuK2cr64wp:3:CL:npdb:default")]
+    #[case("Dk1fRRTtNazyMuK2cr64wp:3:BLS:104:default")]
+    #[case("Dk1fRRTtNazyMuK2cr64wp:4:BDrEcHc8Tb4Lb2VyQZWEDE:3:CL:104:revocable:CL_ACCUM:a4e25e54")]
+    fn invalid_legacy_ids(#[case] legacy_id: &str) {
+        assert!(matches!(
+            to_did_url("idunion", legacy_id),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+    }
+}
@@ -15,8 +15,8 @@ static INDY_UNQUALIFIED_DID_PATTERN: &str =
 static OBJECT_FAMILY_PATTERN: &str = "([a-z]*)";
 static OBJECT_FAMILY_VERSION_PATTERN: &str = "([a-zA-Z0-9]*)";
 
-static ANONCREDSV0_OBJECTS_PATTERN: &str =
-    "(SCHEMA|CLAIM_DEF|REV_REG_DEF|REV_REG_ENTRY|REV_REG_DELTA)";
+static OBJECT_TYPES_PATTERN: &str =
+    "(SCHEMA|CLAIM_DEF|REV_REG_DEF|REV_REG_ENTRY|REV_REG_DELTA|TXN)";
 
 static CLIENT_DEFINED_NAME_PATTERN: &str = "([\\w -]*)";
 static SEQ_NO_PATTERN: &str = "(\\d*)";
@@ -28,8 +28,14 @@ pub enum QueryParameter {
     VersionTime,
     From,
     To,
+    ResourceType,
+    Service,
+    RelativeRef,
 }
 
+/// Value of the `resourceType` query parameter requesting an AnonCreds revocation status list.
+pub const REVOCATION_STATUS_LIST: &str = "anonCredsStatusList";
+
 impl QueryParameter {
     pub fn from_str(input: &str) -> DidIndyResult<QueryParameter> {
         match input {
@@ -37,6 +43,9 @@ impl QueryParameter {
             "versionTime" => Ok(QueryParameter::VersionTime),
             "from" => Ok(QueryParameter::From),
             "to" => Ok(QueryParameter::To),
+            "resourceType" => Ok(QueryParameter::ResourceType),
+            "service" => Ok(QueryParameter::Service),
+            "relativeRef" => Ok(QueryParameter::RelativeRef),
             _ => Err(DidIndyError::QueryParameterNotSupported),
         }
     }
@@ -45,12 +54,14 @@ impl QueryParameter {
 #[derive(Debug, PartialEq)]
 pub enum ObjectFamily {
     Anoncreds,
+    Ledger,
 }
 
 impl ObjectFamily {
     fn from_str(input: &str) -> DidIndyResult<ObjectFamily> {
         match input {
             "anoncreds" => Ok(ObjectFamily::Anoncreds),
+            "ledger" => Ok(ObjectFamily::Ledger),
             _ => Err(DidIndyError::ObjectFamilyNotSupported),
         }
     }
@@ -70,6 +81,20 @@ impl Anoncreds {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum Ledger {
+    LedgerV0,
+}
+
+impl Ledger {
+    fn from_str(input: &str) -> DidIndyResult<Ledger> {
+        match input {
+            "v0" => Ok(Ledger::LedgerV0),
+            _ => Err(DidIndyError::VersionNotSupported),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Schema {
     pub name: String,
@@ -189,6 +214,22 @@ impl RevReg {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct Txn {
+    pub seq_no: u32,
+}
+
+impl Txn {
+    fn from_str(input: &str) -> DidIndyResult<Txn> {
+        let re = Regex::new(format!(r"^{}$", SEQ_NO_PATTERN).as_str()).unwrap();
+        re.captures(input)
+            .and_then(|cap| cap.get(1))
+            .and_then(|seq_no| seq_no.as_str().parse::<u32>().ok())
+            .map(|seq_no| Txn { seq_no })
+            .ok_or(DidIndyError::InvalidDidUrl)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum LedgerObject {
     Schema(Schema),
@@ -196,6 +237,7 @@ pub enum LedgerObject {
     RevRegDef(RevReg),
     RevRegEntry(RevReg),
     RevRegDelta(RevReg),
+    Txn(Txn),
 }
 
 impl LedgerObject {
@@ -203,7 +245,7 @@ impl LedgerObject {
         let re = Regex::new(
             format!(
                 r"{}/{}/{}/(.+)?",
-                OBJECT_FAMILY_PATTERN, OBJECT_FAMILY_VERSION_PATTERN, ANONCREDSV0_OBJECTS_PATTERN
+                OBJECT_FAMILY_PATTERN, OBJECT_FAMILY_VERSION_PATTERN, OBJECT_TYPES_PATTERN
             )
             .as_str(),
         )
@@ -249,6 +291,20 @@ impl LedgerObject {
                         }
                     }
                 }
+                ObjectFamily::Ledger => match Ledger::from_str(version)? {
+                    Ledger::LedgerV0 => {
+                        let ledger_object_type_str =
+                            cap.get(3).ok_or(DidIndyError::InvalidDidUrl)?.as_str();
+                        let ledger_object_type_specific_str =
+                            cap.get(4).ok_or(DidIndyError::InvalidDidUrl)?.as_str();
+                        match ledger_object_type_str {
+                            "TXN" => Ok(LedgerObject::Txn(Txn::from_str(
+                                ledger_object_type_specific_str,
+                            )?)),
+                            _ => Err(DidIndyError::InvalidDidUrl),
+                        }
+                    }
+                },
             }
         } else {
             Err(DidIndyError::InvalidDidUrl)
@@ -262,14 +318,33 @@ pub struct DidUrl {
     pub id: DidValue,
     pub path: Option<String>,
     pub query: HashMap<QueryParameter, String>,
+    pub fragment: Option<String>,
     pub url: String,
 }
 
 impl DidUrl {
+    /// The DID without path, query and fragment.
+    pub fn did(&self) -> String {
+        format!("{}:{}:{}", DID_INDY_PREFIX, self.namespace, self.id)
+    }
+
+    /// The DID URL without fragment.
+    pub fn without_fragment(&self) -> &str {
+        self.url.split('#').next().unwrap_or(&self.url)
+    }
+
     pub fn from_str(input: &str) -> DidIndyResult<DidUrl> {
+        // DIDs of other methods are valid, but cannot be resolved
+        let method_regex = Regex::new(r"^did:([a-z0-9]+):").unwrap();
+        if let Some(cap) = method_regex.captures(input.trim()) {
+            if format!("did:{}", &cap[1]) != DID_INDY_PREFIX {
+                return Err(DidIndyError::MethodNotSupported);
+            }
+        }
+
         let did_regex = Regex::new(
             format!(
-                r"{}:{}:{}([^\?]+)?(?:\?(.+))?$",
+                r"{}:{}:{}([^\?#]+)?(?:\?([^#]+))?(?:#(.+))?$",
                 DID_INDY_PREFIX, NAMESPACE_PATTERN, INDY_UNQUALIFIED_DID_PATTERN
             )
             .as_str(),
@@ -296,6 +371,7 @@ impl DidUrl {
                         .get(3)
                         .and_then(|p| Some(decode(p.as_str()).unwrap().to_string())),
                     query: query_pairs,
+                    fragment: cap.get(5).map(|f| f.as_str().to_string()),
                     url: input.to_string(),
                 };
                 Ok(did)
@@ -333,6 +409,26 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn parse_to_txn() {
+        assert_eq!(
+            LedgerObject::from_str("/ledger/v0/TXN/1234").unwrap(),
+            LedgerObject::Txn(Txn { seq_no: 1234 })
+        )
+    }
+
+    #[test]
+    fn parse_txn_with_invalid_seq_no_fails() {
+        assert!(matches!(
+            LedgerObject::from_str("/ledger/v0/TXN/12ab"),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+        assert!(matches!(
+            LedgerObject::from_str("/anoncreds/v0/TXN/1234"),
+            Err(DidIndyError::InvalidDidUrl)
+        ))
+    }
+
     #[test]
     fn parse_to_schema() {
         assert_eq!(
@@ -439,6 +535,7 @@ mod tests {
                     id: DidValue::new("BDrEcHc8Tb4Lb2VyQZWEDE", None),
                     path: None,
                     query: HashMap::new(),
+                    fragment: None,
                     url: String::from("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE"),
                 }
             );
@@ -450,6 +547,7 @@ mod tests {
                     id: DidValue::new("6cgbu8ZPoWTnR5Rv5JcSMB", None),
                     path: None,
                     query: HashMap::new(),
+                    fragment: None,
                     url: String::from("did:indy:sovrin:staging:6cgbu8ZPoWTnR5Rv5JcSMB"),
                 }
             );
@@ -483,6 +581,7 @@ mod tests {
                     id: DidValue::new("BDrEcHc8Tb4Lb2VyQZWEDE", None),
                     path: None,
                     query: q,
+                    fragment: None,
                     url: String::from("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE?versionId=1"),
                 }
             );
@@ -496,6 +595,18 @@ mod tests {
             ));
         }
 
+        #[test]
+        fn parse_did_of_other_method_fails() {
+            assert!(matches!(
+                DidUrl::from_str("did:sov:BDrEcHc8Tb4Lb2VyQZWEDE"),
+                Err(DidIndyError::MethodNotSupported)
+            ));
+            assert!(matches!(
+                DidUrl::from_str("did:indy:idunion:0"),
+                Err(DidIndyError::InvalidDidUrl)
+            ));
+        }
+
         #[test]
         fn parse_did_url_with_path() {
             assert_eq!(
@@ -506,6 +617,7 @@ mod tests {
                     id: DidValue::new("Dk1fRRTtNazyMuK2cr64wp", None),
                     path: Some(String::from("/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54-e028-462b-a4d6-b1d1712d51a1")),
                     query: HashMap::new(),
+                    fragment: None,
                     url: String::from(
                         "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54-e028-462b-a4d6-b1d1712d51a1"
                     ),
@@ -526,11 +638,40 @@ mod tests {
                     id: DidValue::new("Dk1fRRTtNazyMuK2cr64wp", None),
                     path: Some(String::from("/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54-e028-462b-a4d6-b1d1712d51a1")),
                     query: q,
+                    fragment: None,
                     url: String::from(
                         "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54-e028-462b-a4d6-b1d1712d51a1?versionTime=someXmlDateTime"
                     ),
                 }
             );
         }
+
+        #[test]
+        fn parse_did_url_with_fragment() {
+            let mut q = HashMap::new();
+            q.insert(QueryParameter::VersionId, String::from("1"));
+
+            assert_eq!(
+                DidUrl::from_str("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE?versionId=1#verkey")
+                    .unwrap(),
+                DidUrl {
+                    namespace: String::from("idunion"),
+                    id: DidValue::new("BDrEcHc8Tb4Lb2VyQZWEDE", None),
+                    path: None,
+                    query: q,
+                    fragment: Some(String::from("verkey")),
+                    url: String::from("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE?versionId=1#verkey"),
+                }
+            );
+
+            let did_url =
+                DidUrl::from_str("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE#verkey").unwrap();
+            assert_eq!(did_url.path, None);
+            assert_eq!(did_url.fragment, Some(String::from("verkey")));
+            assert_eq!(
+                did_url.without_fragment(),
+                "did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE"
+            );
+        }
     }
 }
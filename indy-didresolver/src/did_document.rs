@@ -1,10 +1,22 @@
 use super::error::{DidIndyError, DidIndyResult};
 use super::responses::Endpoint;
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::str::FromStr;
+use url::Url;
 
 pub const LEGACY_INDY_SERVICE: &str = "endpoint";
 pub const DID_CORE_CONTEXT: &str = "https://www.w3.org/ns/did/v1";
+pub const ED25519_2020_CONTEXT: &str = "https://w3id.org/security/suites/ed25519-2020/v1";
+pub const JWS_2020_CONTEXT: &str = "https://w3id.org/security/suites/jws-2020/v1";
+pub const X25519_2019_CONTEXT: &str = "https://w3id.org/security/suites/x25519-2019/v1";
+pub const X25519_2020_CONTEXT: &str = "https://w3id.org/security/suites/x25519-2020/v1";
+pub const KEY_AGREEMENT_FRAGMENT: &str = "key-agreement-1";
+
+// Multicodec prefixes of ed25519 and x25519 public keys
+const ED25519_MULTICODEC: [u8; 2] = [0xed, 0x01];
+const X25519_MULTICODEC: [u8; 2] = [0xec, 0x01];
 
 #[derive(Serialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -15,21 +27,106 @@ pub struct Ed25519VerificationKey2018 {
     pub public_key_base58: String,
 }
 
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Ed25519VerificationKey2020 {
+    pub id: String,
+    pub type_: String,
+    pub controller: String,
+    pub public_key_multibase: String,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct X25519KeyAgreementKey2019 {
+    pub id: String,
+    pub type_: String,
+    pub controller: String,
+    pub public_key_base58: String,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct X25519KeyAgreementKey2020 {
+    pub id: String,
+    pub type_: String,
+    pub controller: String,
+    pub public_key_multibase: String,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonWebKey2020 {
+    pub id: String,
+    pub type_: String,
+    pub controller: String,
+    pub public_key_jwk: Value,
+}
+
+/// Representation of the verkey in the verification method, selected by the `transformKeys`
+/// resolution option.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum KeyRepresentation {
+    #[default]
+    Ed25519VerificationKey2018,
+    Ed25519VerificationKey2020,
+    JsonWebKey2020,
+}
+
+impl KeyRepresentation {
+    // Ed25519VerificationKey2018 is defined by the DID core context
+    fn context(&self) -> Option<&'static str> {
+        match self {
+            KeyRepresentation::Ed25519VerificationKey2018 => None,
+            KeyRepresentation::Ed25519VerificationKey2020 => Some(ED25519_2020_CONTEXT),
+            KeyRepresentation::JsonWebKey2020 => Some(JWS_2020_CONTEXT),
+        }
+    }
+
+    fn key_agreement_context(&self) -> &'static str {
+        match self {
+            KeyRepresentation::Ed25519VerificationKey2018 => X25519_2019_CONTEXT,
+            KeyRepresentation::Ed25519VerificationKey2020 => X25519_2020_CONTEXT,
+            KeyRepresentation::JsonWebKey2020 => JWS_2020_CONTEXT,
+        }
+    }
+}
+
+impl FromStr for KeyRepresentation {
+    type Err = DidIndyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ed25519VerificationKey2018" => Ok(KeyRepresentation::Ed25519VerificationKey2018),
+            "Ed25519VerificationKey2020" => Ok(KeyRepresentation::Ed25519VerificationKey2020),
+            "JsonWebKey2020" => Ok(KeyRepresentation::JsonWebKey2020),
+            _ => Err(DidIndyError::RepresentationNotSupported),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DidCommService {
     pub id: String,
     pub type_: String,
+    pub service_endpoint: String,
     pub recipient_keys: Vec<String>,
     pub routing_keys: Vec<String>,
     pub priority: u8,
 }
 
 impl DidCommService {
-    pub fn new(id: String, recipient_keys: Vec<String>, routing_keys: Vec<String>) -> Self {
+    pub fn new(
+        id: String,
+        service_endpoint: String,
+        recipient_keys: Vec<String>,
+        routing_keys: Vec<String>,
+    ) -> Self {
         Self {
             id,
             type_: "did-communication".to_string(),
+            service_endpoint,
             recipient_keys,
             routing_keys,
             priority: 0,
@@ -51,7 +148,7 @@ pub enum Service {
     DidCommService(DidCommService),
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DidDocument {
     namespace: String,
@@ -59,6 +156,8 @@ pub struct DidDocument {
     verkey: String,
     endpoint: Option<Endpoint>,
     diddoc_content: Option<Value>,
+    #[serde(default)]
+    deactivated: bool,
 }
 
 // Returns raw verkey in case of errors, otherwise 'default' indy handling
@@ -123,21 +222,55 @@ impl DidDocument {
             verkey: expand_verkey(id, verkey),
             endpoint,
             diddoc_content,
+            deactivated: false,
         }
     }
 
+    // Deactivated DIDs resolve to a document without verification methods and services
+    pub fn new_deactivated(namespace: &str, id: &str) -> Self {
+        DidDocument {
+            namespace: namespace.to_string(),
+            id: id.to_string(),
+            verkey: String::new(),
+            endpoint: None,
+            diddoc_content: None,
+            deactivated: true,
+        }
+    }
+
+    pub fn is_deactivated(&self) -> bool {
+        self.deactivated
+    }
+
     pub fn to_value(&self) -> DidIndyResult<Value> {
+        self.to_value_with_keys(KeyRepresentation::default())
+    }
+
+    pub fn to_value_with_keys(&self, keys: KeyRepresentation) -> DidIndyResult<Value> {
+        if self.deactivated {
+            return Ok(json!({
+                "id": format!("did:indy:{}:{}", self.namespace, self.id),
+            }));
+        }
+
         let mut doc = json!({
              "id": format!("did:indy:{}:{}", self.namespace, self.id),
-            "verificationMethod": [Ed25519VerificationKey2018 {
-                id: format!("did:indy:{}:{}#verkey", self.namespace, self.id),
-                type_: format!("Ed25519VerificationKey2018"),
-                controller: format!("did:indy:{}:{}", self.namespace, self.id),
-                public_key_base58: format!("{}", self.verkey),
-            }],
+            "verificationMethod": [self.verification_method(keys)?],
             "authentication": [format!("did:indy:{}:{}#verkey", self.namespace, self.id)],
         });
 
+        // diddocContent with its own key agreement replaces the derived one
+        let derive_key_agreement =
+            !matches!(&self.diddoc_content, Some(content) if defines_key_agreement(content));
+        let key_agreement = self.key_agreement(keys).filter(|_| derive_key_agreement);
+        let has_key_agreement = key_agreement.is_some();
+        if let Some(key_agreement) = key_agreement {
+            doc["keyAgreement"] = json!([key_agreement["id"]]);
+            if let Some(methods) = doc["verificationMethod"].as_array_mut() {
+                methods.push(key_agreement);
+            }
+        }
+
         if self.diddoc_content.is_some() {
             let is_valid = validate_diddoc_content(&(self.diddoc_content.as_ref().unwrap()));
 
@@ -155,6 +288,7 @@ impl DidDocument {
                 let s = match service.as_str() {
                     LEGACY_INDY_SERVICE => json!(DidCommService::new(
                         format!("did:indy:{}:{}#did-communication", self.namespace, self.id),
+                        service_endpoint,
                         vec![format!("did:indy:{}:{}#verkey", self.namespace, self.id)],
                         vec![],
                     )),
@@ -172,21 +306,202 @@ impl DidDocument {
             }
         }
 
+        if let Some(context) = keys.context() {
+            add_context(&mut doc, context);
+        }
+        // Documents without JSON-LD context are plain JSON and need no term definitions
+        if has_key_agreement && doc.get("@context").is_some() {
+            add_context(&mut doc, keys.key_agreement_context());
+        }
+
         Ok(doc)
     }
 
+    fn verification_method(&self, keys: KeyRepresentation) -> DidIndyResult<Value> {
+        let id = format!("did:indy:{}:{}#verkey", self.namespace, self.id);
+        let controller = format!("did:indy:{}:{}", self.namespace, self.id);
+        let method = match keys {
+            KeyRepresentation::Ed25519VerificationKey2018 => json!(Ed25519VerificationKey2018 {
+                id,
+                type_: format!("Ed25519VerificationKey2018"),
+                controller,
+                public_key_base58: format!("{}", self.verkey),
+            }),
+            KeyRepresentation::Ed25519VerificationKey2020 => json!(Ed25519VerificationKey2020 {
+                id,
+                type_: String::from("Ed25519VerificationKey2020"),
+                controller,
+                public_key_multibase: to_multibase(
+                    &ED25519_MULTICODEC,
+                    &decode_verkey(&self.verkey)?
+                ),
+            }),
+            KeyRepresentation::JsonWebKey2020 => json!(JsonWebKey2020 {
+                id,
+                type_: String::from("JsonWebKey2020"),
+                controller,
+                public_key_jwk: to_jwk("Ed25519", &decode_verkey(&self.verkey)?),
+            }),
+        };
+        Ok(method)
+    }
+
+    // X25519 key for DIDComm encryption, converted from the verkey. Verkeys which are no valid
+    // ed25519 keys resolve without key agreement.
+    fn key_agreement(&self, keys: KeyRepresentation) -> Option<Value> {
+        let key = decode_verkey(&self.verkey)
+            .and_then(|key| to_x25519(&key))
+            .ok()?;
+        let id = format!(
+            "did:indy:{}:{}#{}",
+            self.namespace, self.id, KEY_AGREEMENT_FRAGMENT
+        );
+        let controller = format!("did:indy:{}:{}", self.namespace, self.id);
+        let method = match keys {
+            KeyRepresentation::Ed25519VerificationKey2018 => json!(X25519KeyAgreementKey2019 {
+                id,
+                type_: String::from("X25519KeyAgreementKey2019"),
+                controller,
+                public_key_base58: bs58::encode(key).into_string(),
+            }),
+            KeyRepresentation::Ed25519VerificationKey2020 => json!(X25519KeyAgreementKey2020 {
+                id,
+                type_: String::from("X25519KeyAgreementKey2020"),
+                controller,
+                public_key_multibase: to_multibase(&X25519_MULTICODEC, &key),
+            }),
+            KeyRepresentation::JsonWebKey2020 => json!(JsonWebKey2020 {
+                id,
+                type_: String::from("JsonWebKey2020"),
+                controller,
+                public_key_jwk: to_jwk("X25519", &key),
+            }),
+        };
+        Some(method)
+    }
+
     pub fn to_string(&self) -> DidIndyResult<String> {
         let doc = self.to_value()?;
         Ok(serde_json::to_string_pretty(&doc).unwrap())
     }
 }
 
+// Expanded verkeys may carry their crypto type as suffix, only ed25519 keys are supported
+fn decode_verkey(verkey: &str) -> DidIndyResult<Vec<u8>> {
+    let mut parts = verkey.split(':');
+    let key = parts.next().unwrap_or_default();
+    if parts.any(|key_type| key_type != "ed25519") {
+        return Err(DidIndyError::UnexpectedKeyFormat);
+    }
+    let key = bs58::decode(key).into_vec()?;
+    if key.len() != 32 {
+        return Err(DidIndyError::UnexpectedKeyFormat);
+    }
+    Ok(key)
+}
+
+// Montgomery form of the ed25519 public key, i.e. the birational map to Curve25519
+fn to_x25519(key: &[u8]) -> DidIndyResult<[u8; 32]> {
+    CompressedEdwardsY::from_slice(key)
+        .ok()
+        .and_then(|key| key.decompress())
+        .map(|key| key.to_montgomery().to_bytes())
+        .ok_or(DidIndyError::UnexpectedKeyFormat)
+}
+
+// Base58btc multibase of the key prefixed with its multicodec
+fn to_multibase(codec: &[u8], key: &[u8]) -> String {
+    let prefixed: Vec<u8> = codec.iter().chain(key).copied().collect();
+    format!("z{}", bs58::encode(prefixed).into_string())
+}
+
+fn to_jwk(curve: &str, key: &[u8]) -> Value {
+    json!({
+        "kty": "OKP",
+        "crv": curve,
+        "x": base64::encode_config(key, base64::URL_SAFE_NO_PAD),
+    })
+}
+
+// Keeps contexts from diddocContent and adds `context` if missing
+fn add_context(doc: &mut Value, context: &str) {
+    let mut contexts = match doc.get("@context") {
+        Some(Value::Array(contexts)) => contexts.clone(),
+        Some(existing) => vec![existing.clone()],
+        None => vec![Value::from(DID_CORE_CONTEXT)],
+    };
+    if !contexts.iter().any(|existing| existing == context) {
+        contexts.push(Value::from(context));
+    }
+    doc["@context"] = Value::Array(contexts);
+}
+
+/// Returns the verification method or service of a DID document identified by `fragment`.
+/// Ids can be absolute (`<did>#<fragment>`) or relative (`#<fragment>`).
+pub fn find_by_fragment(did_document: &Value, fragment: &str) -> Option<Value> {
+    find_entry(did_document, &["verificationMethod", "service"], fragment)
+}
+
+/// Returns the endpoint URL of the service identified by `service`, resolving `relative_ref`
+/// against it as defined by RFC 3986.
+pub fn service_endpoint(
+    did_document: &Value,
+    service: &str,
+    relative_ref: Option<&str>,
+) -> DidIndyResult<String> {
+    let service = find_entry(did_document, &["service"], service).ok_or(DidIndyError::NotFound)?;
+    // DIDComm v2 services wrap the endpoint URL in an object
+    let endpoint = match &service["serviceEndpoint"] {
+        Value::String(endpoint) => endpoint.as_str(),
+        Value::Object(endpoint) => endpoint
+            .get("uri")
+            .and_then(Value::as_str)
+            .ok_or(DidIndyError::NotFound)?,
+        _ => return Err(DidIndyError::NotFound),
+    };
+    match relative_ref {
+        Some(relative_ref) => Url::parse(endpoint)
+            .and_then(|url| url.join(relative_ref))
+            .map(String::from)
+            .map_err(|_| DidIndyError::InvalidDidUrl),
+        None => Ok(endpoint.to_string()),
+    }
+}
+
+fn find_entry(did_document: &Value, properties: &[&str], fragment: &str) -> Option<Value> {
+    let did = did_document["id"].as_str()?;
+    let absolute = format!("{}#{}", did, fragment);
+    let relative = format!("#{}", fragment);
+    properties
+        .iter()
+        .filter_map(|property| did_document[*property].as_array())
+        .flatten()
+        .find(|entry| matches!(entry["id"].as_str(), Some(id) if id == absolute || id == relative))
+        .cloned()
+}
+
+// The ledger stores diddocContent as JSON string, older replies might contain the object itself
+pub fn parse_diddoc_content(diddoc_content: &Value) -> DidIndyResult<Value> {
+    let content = match diddoc_content {
+        Value::String(content) => {
+            serde_json::from_str(content).map_err(|_| DidIndyError::InvalidDidDoc)?
+        }
+        content => content.to_owned(),
+    };
+
+    if validate_diddoc_content(&content) {
+        Ok(content)
+    } else {
+        Err(DidIndyError::InvalidDidDoc)
+    }
+}
+
 fn validate_context(context: &str) -> bool {
     context == DID_CORE_CONTEXT
 }
 
 fn validate_diddoc_content(diddoc_content: &Value) -> bool {
-    if diddoc_content.get("id").is_some() {
+    if !diddoc_content.is_object() || diddoc_content.get("id").is_some() {
         false
     } else if diddoc_content.get("@context").is_some() {
         let context = diddoc_content.get("@context").unwrap();
@@ -199,7 +514,7 @@ fn validate_diddoc_content(diddoc_content: &Value) -> bool {
                 if buf {
                     return buf;
                 }
-                buf = validate_context(c.as_str().unwrap());
+                buf = c.as_str().map(validate_context).unwrap_or(false);
             }
             buf
         } else {
@@ -210,14 +525,29 @@ fn validate_diddoc_content(diddoc_content: &Value) -> bool {
     }
 }
 
+fn defines_key_agreement(content: &Value) -> bool {
+    let fragment = format!("#{}", KEY_AGREEMENT_FRAGMENT);
+    let has_fragment =
+        |method: &Value| matches!(method["id"].as_str(), Some(id) if id.ends_with(&fragment));
+    content.get("keyAgreement").is_some()
+        || match &content["verificationMethod"] {
+            Value::Array(methods) => methods.iter().any(has_fragment),
+            method => has_fragment(method),
+        }
+}
+
 fn merge_diddoc(base: &mut Value, content: &Value) {
     match (base, content) {
         (Value::Object(base), Value::Object(content)) => {
             for (k, v) in content {
-                if k == "authentication" || k == "verificationMethod" {
-                    let mut _tmp = base[k].as_array().unwrap().to_owned();
+                if k == "authentication" || k == "verificationMethod" || k == "keyAgreement" {
+                    let mut _tmp = base
+                        .get(k)
+                        .and_then(Value::as_array)
+                        .cloned()
+                        .unwrap_or_default();
                     _tmp.append(&mut v.as_array().unwrap_or(&vec![v.to_owned()]).to_owned());
-                    base[k] = Value::from(_tmp);
+                    base.insert(k.clone(), Value::from(_tmp));
                 } else {
                     merge_diddoc(base.entry(k).or_insert(Value::Null), v);
                 }
@@ -296,7 +626,13 @@ mod tests {
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            },{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "GfptF5WpR9sqqGZY7ot1WQce9B7W7zunjjC9qhjmiAje",
             }],
+            "keyAgreement": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1"],
             "authentication": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"],
         });
 
@@ -340,7 +676,8 @@ mod tests {
         let serialized = json!({
             "@context": [
               "https://www.w3.org/ns/did/v1",
-               "https://identity.foundation/didcomm-messaging/service-endpoint/v1"
+               "https://identity.foundation/didcomm-messaging/service-endpoint/v1",
+               X25519_2019_CONTEXT
             ],
             "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
             "verificationMethod": [{
@@ -348,7 +685,13 @@ mod tests {
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            },{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "GfptF5WpR9sqqGZY7ot1WQce9B7W7zunjjC9qhjmiAje",
             }],
+            "keyAgreement": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1"],
             "authentication": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"],
             "service": [{
                 "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#did-communication",
@@ -403,7 +746,8 @@ mod tests {
         let serialized = json!({
             "@context": [
               "https://www.w3.org/ns/did/v1",
-               "https://identity.foundation/didcomm-messaging/service-endpoint/v1"
+               "https://identity.foundation/didcomm-messaging/service-endpoint/v1",
+               X25519_2019_CONTEXT
             ],
             "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
             "verificationMethod": [{
@@ -411,12 +755,18 @@ mod tests {
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            },{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "GfptF5WpR9sqqGZY7ot1WQce9B7W7zunjjC9qhjmiAje",
             },{
                 "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#keys-2",
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBc",
             }],
+            "keyAgreement": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1"],
             "authentication": [
                 "did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey",
                 "did:indy:idunion:QowxFtwciWceMFr7WbwnM#keys-2"],
@@ -473,7 +823,8 @@ mod tests {
         let serialized = json!({
             "@context": [
               "https://www.w3.org/ns/did/v1",
-               "https://identity.foundation/didcomm-messaging/service-endpoint/v1"
+               "https://identity.foundation/didcomm-messaging/service-endpoint/v1",
+               X25519_2019_CONTEXT
             ],
             "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
             "verificationMethod": [{
@@ -481,12 +832,18 @@ mod tests {
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            },{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "GfptF5WpR9sqqGZY7ot1WQce9B7W7zunjjC9qhjmiAje",
             },{
                 "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#keys-2",
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBc",
             }],
+            "keyAgreement": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1"],
             "authentication": [
                 "did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey",
                 "did:indy:idunion:QowxFtwciWceMFr7WbwnM#keys-2"
@@ -531,11 +888,18 @@ mod tests {
                 "type": "Ed25519VerificationKey2018",
                 "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
                 "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            },{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "GfptF5WpR9sqqGZY7ot1WQce9B7W7zunjjC9qhjmiAje",
             }],
+            "keyAgreement": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1"],
             "authentication": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"],
             "service": [{
                 "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#did-communication",
                 "type": "did-communication",
+                "serviceEndpoint": "https://agent.com",
                 "recipientKeys": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"],
                 "routingKeys": [],
                 "priority": 0
@@ -574,6 +938,173 @@ mod tests {
         assert_eq!(2, v_from_doc["service"].as_array().unwrap().len())
     }
 
+    #[test]
+    fn serialze_deactivated_diddoc() {
+        let doc = DidDocument::new_deactivated("idunion", "QowxFtwciWceMFr7WbwnM");
+
+        assert!(doc.is_deactivated());
+        assert_eq!(
+            doc.to_value().unwrap(),
+            json!({ "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM" })
+        )
+    }
+
+    #[test]
+    fn serialze_diddoc_with_ed25519_2020_key() {
+        let doc = DidDocument::new(
+            "idunion",
+            "QowxFtwciWceMFr7WbwnM",
+            "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            None,
+            None,
+        );
+
+        assert_eq!(
+            doc.to_value_with_keys(KeyRepresentation::Ed25519VerificationKey2020)
+                .unwrap(),
+            json!({
+                "@context": [DID_CORE_CONTEXT, ED25519_2020_CONTEXT, X25519_2020_CONTEXT],
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "verificationMethod": [{
+                    "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey",
+                    "type": "Ed25519VerificationKey2020",
+                    "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                    "publicKeyMultibase": "z6MkjaEG89BXgrWgDysSw1HxkSq6f3AheUdFSedPasiATWy8",
+                },{
+                    "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+                    "type": "X25519KeyAgreementKey2020",
+                    "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                    "publicKeyMultibase": "z6LSsM13mPKgWcbavewJeTPxpzq7zKecpc5wchuqLAPJRYWQ",
+                }],
+                "authentication": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"],
+                "keyAgreement": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1"],
+            })
+        )
+    }
+
+    #[test]
+    fn serialze_diddoc_with_json_web_key_keeps_diddoc_content_context() {
+        let doc = DidDocument::new(
+            "idunion",
+            "QowxFtwciWceMFr7WbwnM",
+            "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk:ed25519",
+            None,
+            Some(json!({ "@context": DID_CORE_CONTEXT })),
+        );
+
+        let doc = doc
+            .to_value_with_keys(KeyRepresentation::JsonWebKey2020)
+            .unwrap();
+        assert_eq!(doc["@context"], json!([DID_CORE_CONTEXT, JWS_2020_CONTEXT]));
+        assert_eq!(
+            doc["verificationMethod"][0],
+            json!({
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey",
+                "type": "JsonWebKey2020",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyJwk": {
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": "TBN7s7vx0PteZcowQeCQ3Ul6t-kfqP-8crwMncCLSws",
+                },
+            })
+        );
+        assert_eq!(
+            doc["verificationMethod"][1]["publicKeyJwk"],
+            json!({
+                "kty": "OKP",
+                "crv": "X25519",
+                "x": "6NGERTC0y-Ygilw9oW6INkpwzdz7GOTWtn-LZkGTW1U",
+            })
+        )
+    }
+
+    #[test]
+    fn serialze_diddoc_without_key_agreement_for_invalid_verkey() {
+        let doc = DidDocument::new(
+            "idunion",
+            "QowxFtwciWceMFr7WbwnM",
+            "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk:secp256k1",
+            None,
+            None,
+        );
+
+        let doc = doc.to_value().unwrap();
+        assert!(doc.get("keyAgreement").is_none());
+        assert_eq!(doc["verificationMethod"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn serialze_diddoc_without_derived_key_agreement_if_defined_by_diddoc_content() {
+        let key_agreement = json!({
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#key-agreement-1",
+            "type": "X25519KeyAgreementKey2019",
+            "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "publicKeyBase58": "9hFgmPVfmBZwRvFEyniQDBkz9LmV7gDEqytWyGZLmDXE"
+        });
+        for diddoc_content in [
+            json!({ "keyAgreement": [key_agreement] }),
+            json!({ "verificationMethod": [key_agreement] }),
+        ] {
+            let doc = DidDocument::new(
+                "idunion",
+                "QowxFtwciWceMFr7WbwnM",
+                "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+                None,
+                Some(diddoc_content),
+            );
+
+            let doc = doc.to_value().unwrap();
+            let ids: Vec<&Value> = doc["verificationMethod"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .chain(doc["keyAgreement"].as_array().into_iter().flatten())
+                .map(|method| &method["id"])
+                .filter(|id| id.as_str() == key_agreement["id"].as_str())
+                .collect();
+            assert_eq!(ids.len(), 1);
+        }
+    }
+
+    #[test]
+    fn serialze_diddoc_with_key_agreement_context_only_if_derived() {
+        let diddoc_content = json!({
+            "keyAgreement": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#keys-2",
+                "type": "X25519KeyAgreementKey2019",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "9hFgmPVfmBZwRvFEyniQDBkz9LmV7gDEqytWyGZLmDXE"
+            }]
+        });
+        let doc = DidDocument::new(
+            "idunion",
+            "QowxFtwciWceMFr7WbwnM",
+            "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            None,
+            Some(diddoc_content),
+        );
+
+        assert_eq!(
+            doc.to_value_with_keys(KeyRepresentation::Ed25519VerificationKey2020)
+                .unwrap()["@context"],
+            json!([DID_CORE_CONTEXT, ED25519_2020_CONTEXT])
+        );
+        assert!(doc.to_value().unwrap().get("@context").is_none());
+    }
+
+    #[test]
+    fn transform_keys_rejects_unknown_representation() {
+        assert_eq!(
+            KeyRepresentation::from_str("JsonWebKey2020").unwrap(),
+            KeyRepresentation::JsonWebKey2020
+        );
+        assert!(matches!(
+            KeyRepresentation::from_str("EcdsaSecp256k1VerificationKey2019"),
+            Err(DidIndyError::RepresentationNotSupported)
+        ));
+    }
+
     #[test]
     fn validate_diddoc_with_context_as_string() {
         let diddoc_content = json!({
@@ -625,6 +1156,56 @@ mod tests {
         assert!(!validate_diddoc_content(&diddoc_content))
     }
 
+    #[test]
+    fn validate_diddoc_with_context_not_a_string() {
+        let diddoc_content = json!({
+            "@context" : [{ "@vocab": "https://example.com" }],
+        });
+        assert!(!validate_diddoc_content(&diddoc_content))
+    }
+
+    #[test]
+    fn validate_diddoc_not_an_object() {
+        let diddoc_content = json!(["https://www.w3.org/ns/did/v1"]);
+        assert!(!validate_diddoc_content(&diddoc_content))
+    }
+
+    #[test]
+    fn parse_diddoc_content_from_string() {
+        let diddoc_content = json!({
+            "@context" : "https://www.w3.org/ns/did/v1",
+            "service": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#did-communication",
+                "type": "did-communication",
+                "serviceEndpoint": "https://example.com",
+            }]
+        });
+        let raw = Value::String(diddoc_content.to_string());
+
+        assert_eq!(parse_diddoc_content(&raw).unwrap(), diddoc_content)
+    }
+
+    #[test]
+    fn parse_diddoc_content_fails_with_invalid_json() {
+        let raw = Value::String(String::from("{\"service\": ["));
+
+        assert!(matches!(
+            parse_diddoc_content(&raw),
+            Err(DidIndyError::InvalidDidDoc)
+        ))
+    }
+
+    #[test]
+    fn parse_diddoc_content_fails_with_id() {
+        let raw =
+            Value::String(json!({ "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM" }).to_string());
+
+        assert!(matches!(
+            parse_diddoc_content(&raw),
+            Err(DidIndyError::InvalidDidDoc)
+        ))
+    }
+
     #[test]
     fn validate_diddoc_with_id() {
         let diddoc_content = json!({
@@ -632,4 +1213,103 @@ mod tests {
         });
         assert!(!validate_diddoc_content(&diddoc_content))
     }
+
+    #[test]
+    fn find_verification_method_and_service_by_fragment() {
+        let doc = json!({
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "verificationMethod": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey",
+                "type": "Ed25519VerificationKey2018",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "6bA9ErmZ4aqtz1YUhDAodQtbgo8TNJbdTZ6jMxAYmgSy"
+            }, {
+                "id": "#keys-2",
+                "type": "Ed25519VerificationKey2018",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "9wvq2i4xUa5umXoThe83CDgx1e5bsjZKJL4DEWvTP9qe"
+            }],
+            "service": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#did-communication",
+                "type": "did-communication",
+                "serviceEndpoint": "https://example.com"
+            }]
+        });
+
+        assert_eq!(
+            find_by_fragment(&doc, "verkey"),
+            Some(doc["verificationMethod"][0].clone())
+        );
+        assert_eq!(
+            find_by_fragment(&doc, "keys-2"),
+            Some(doc["verificationMethod"][1].clone())
+        );
+        assert_eq!(
+            find_by_fragment(&doc, "did-communication"),
+            Some(doc["service"][0].clone())
+        );
+        assert_eq!(find_by_fragment(&doc, "unknown"), None);
+    }
+
+    #[test]
+    fn compose_service_endpoint_with_relative_ref() {
+        let mut endpoint_map: HashMap<String, String> = HashMap::new();
+        endpoint_map.insert(String::from("endpoint"), String::from("https://agent.com"));
+        endpoint_map.insert(
+            String::from("profile"),
+            String::from("https://agent.com/profile/"),
+        );
+        let doc = DidDocument::new(
+            "idunion",
+            "QowxFtwciWceMFr7WbwnM",
+            "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            Some(Endpoint {
+                endpoint: endpoint_map,
+            }),
+            None,
+        )
+        .to_value()
+        .unwrap();
+
+        assert_eq!(
+            service_endpoint(&doc, "profile", None).unwrap(),
+            "https://agent.com/profile/"
+        );
+        assert_eq!(
+            service_endpoint(&doc, "profile", Some("inbox?page=2")).unwrap(),
+            "https://agent.com/profile/inbox?page=2"
+        );
+        assert_eq!(
+            service_endpoint(&doc, "profile", Some("/inbox")).unwrap(),
+            "https://agent.com/inbox"
+        );
+        assert_eq!(
+            service_endpoint(&doc, "did-communication", None).unwrap(),
+            "https://agent.com"
+        );
+        assert_eq!(
+            service_endpoint(&doc, "did-communication", Some("/inbox")).unwrap(),
+            "https://agent.com/inbox"
+        );
+        assert!(matches!(
+            service_endpoint(&doc, "verkey", None),
+            Err(DidIndyError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn compose_didcomm_v2_service_endpoint() {
+        let doc = json!({
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "service": [{
+                "id": "#didcomm",
+                "type": "DIDCommMessaging",
+                "serviceEndpoint": {"uri": "https://example.com/didcomm"}
+            }]
+        });
+        assert_eq!(
+            service_endpoint(&doc, "didcomm", None).unwrap(),
+            "https://example.com/didcomm"
+        );
+    }
 }
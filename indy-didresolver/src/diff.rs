@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use urlencoding::encode;
+
+// Properties with entries identified by their id, all others are compared as a whole
+static VERIFICATION_METHOD: &str = "verificationMethod";
+static SERVICE: &str = "service";
+
+/// A version of a DID document, selected by `versionId` or `versionTime`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Version {
+    Id(u32),
+    Time(String),
+}
+
+impl Version {
+    /// The DID URL query selecting this version.
+    pub fn to_query(&self) -> String {
+        match self {
+            Version::Id(seq_no) => format!("versionId={}", seq_no),
+            Version::Time(time) => format!("versionTime={}", encode(time)),
+        }
+    }
+}
+
+/// Changes between two versions of a DID document.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DidDocumentDiff {
+    pub verification_methods: EntryDiff,
+    pub services: EntryDiff,
+    /// Changes of all other properties, e.g. from edited diddocContent.
+    pub properties: Vec<PropertyChange>,
+}
+
+impl DidDocumentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.verification_methods.is_empty()
+            && self.services.is_empty()
+            && self.properties.is_empty()
+    }
+}
+
+/// Entries of a property like `verificationMethod`, matched by their `id`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryDiff {
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<EntryChange>,
+}
+
+impl EntryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryChange {
+    pub id: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+/// A top level property that was added (`from` is None), removed (`to` is None) or changed.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyChange {
+    pub property: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Value>,
+}
+
+/// Compares two DID documents in their JSON representation.
+pub fn diff(from: &Value, to: &Value) -> DidDocumentDiff {
+    let mut properties: Vec<String> = property_names(from)
+        .chain(property_names(to))
+        .filter(|property| property != VERIFICATION_METHOD && property != SERVICE)
+        .collect();
+    properties.sort();
+    properties.dedup();
+
+    DidDocumentDiff {
+        verification_methods: diff_entries(&from[VERIFICATION_METHOD], &to[VERIFICATION_METHOD]),
+        services: diff_entries(&from[SERVICE], &to[SERVICE]),
+        properties: properties
+            .into_iter()
+            .filter(|property| from.get(property) != to.get(property))
+            .map(|property| PropertyChange {
+                from: from.get(&property).cloned(),
+                to: to.get(&property).cloned(),
+                property,
+            })
+            .collect(),
+    }
+}
+
+fn property_names(doc: &Value) -> impl Iterator<Item = String> + '_ {
+    doc.as_object()
+        .into_iter()
+        .flat_map(|doc| doc.keys().cloned())
+}
+
+fn diff_entries(from: &Value, to: &Value) -> EntryDiff {
+    let from = entries(from);
+    let to = entries(to);
+    let find = |entries: &[(String, Value)], id: &str| {
+        entries
+            .iter()
+            .find(|(entry_id, _)| entry_id == id)
+            .map(|(_, entry)| entry.clone())
+    };
+
+    let mut entry_diff = EntryDiff::default();
+    for (id, entry) in &from {
+        match find(&to, id) {
+            None => entry_diff.removed.push(entry.clone()),
+            Some(to_entry) if to_entry != *entry => entry_diff.changed.push(EntryChange {
+                id: id.clone(),
+                from: entry.clone(),
+                to: to_entry,
+            }),
+            Some(_) => {}
+        }
+    }
+    for (id, entry) in &to {
+        if find(&from, id).is_none() {
+            entry_diff.added.push(entry.clone());
+        }
+    }
+    entry_diff
+}
+
+// Entries without id can only be told apart by their content
+fn entries(property: &Value) -> Vec<(String, Value)> {
+    property
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|entry| {
+            let id = match entry["id"].as_str() {
+                Some(id) => id.to_string(),
+                None => entry.to_string(),
+            };
+            (id, entry.clone())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn verkey(id: &str, key: &str) -> Value {
+        json!({
+            "id": format!("did:indy:idunion:QowxFtwciWceMFr7WbwnM#{}", id),
+            "type": "Ed25519VerificationKey2018",
+            "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "publicKeyBase58": key
+        })
+    }
+
+    #[test]
+    fn diff_rotated_verkey_and_services() {
+        let from = json!({
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "verificationMethod": [verkey("verkey", "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk")],
+            "authentication": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"],
+            "service": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#did-communication",
+                "type": "did-communication",
+                "serviceEndpoint": "https://agent.com"
+            }]
+        });
+        let to = json!({
+            "@context": ["https://www.w3.org/ns/did/v1"],
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "verificationMethod": [
+                verkey("verkey", "9wvq2i4xUa5umXoThe83CDgx1e5bsjZKJL4DEWvTP9qe"),
+                verkey("keys-2", "6bA9ErmZ4aqtz1YUhDAodQtbgo8TNJbdTZ6jMxAYmgSy")
+            ],
+            "authentication": ["did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"]
+        });
+
+        let diff = diff(&from, &to);
+
+        assert_eq!(
+            diff.verification_methods,
+            EntryDiff {
+                added: vec![verkey(
+                    "keys-2",
+                    "6bA9ErmZ4aqtz1YUhDAodQtbgo8TNJbdTZ6jMxAYmgSy"
+                )],
+                removed: vec![],
+                changed: vec![EntryChange {
+                    id: String::from("did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey"),
+                    from: verkey("verkey", "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk"),
+                    to: verkey("verkey", "9wvq2i4xUa5umXoThe83CDgx1e5bsjZKJL4DEWvTP9qe"),
+                }],
+            }
+        );
+        assert_eq!(diff.services.removed, vec![from["service"][0].clone()]);
+        assert!(diff.services.added.is_empty());
+        assert_eq!(
+            diff.properties,
+            vec![PropertyChange {
+                property: String::from("@context"),
+                from: None,
+                to: Some(json!(["https://www.w3.org/ns/did/v1"])),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_of_identical_documents_is_empty() {
+        let doc = json!({
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "verificationMethod": [verkey("verkey", "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk")],
+        });
+        assert!(diff(&doc, &doc).is_empty());
+    }
+
+    #[test]
+    fn version_query() {
+        assert_eq!(Version::Id(42).to_query(), "versionId=42");
+        assert_eq!(
+            Version::Time(String::from("2021-12-20T19:17:47+01:00")).to_query(),
+            "versionTime=2021-12-20T19%3A17%3A47%2B01%3A00"
+        );
+    }
+}
@@ -3,12 +3,21 @@ use thiserror::Error;
 
 pub type DidIndyResult<T> = std::result::Result<T, DidIndyError>;
 
+// Error codes of the DID Resolution specification
+pub const INVALID_DID: &str = "invalidDid";
+pub const NOT_FOUND: &str = "notFound";
+pub const METHOD_NOT_SUPPORTED: &str = "methodNotSupported";
+pub const REPRESENTATION_NOT_SUPPORTED: &str = "representationNotSupported";
+pub const INTERNAL_ERROR: &str = "internalError";
+
 #[derive(Debug, Error)]
 pub enum DidIndyError {
     #[error("Parsing error")]
     ParsingError(#[from] serde_json::Error),
     #[error("Could not parse datetime")]
     DateTimeError(#[from] chrono::ParseError),
+    #[error("DID method not supported")]
+    MethodNotSupported,
     #[error("Namespace not supported")]
     NamespaceNotSupported,
     #[error("Query parameter not supported")]
@@ -34,7 +43,41 @@ pub enum DidIndyError {
     #[error("Base58 Parsing error")]
     FromBase58Error(#[from] bs58::decode::Error),
     #[error("Unexpected Key Format")]
-    UnexpectedKeyFormat
+    UnexpectedKeyFormat,
+    #[error("Representation not supported")]
+    RepresentationNotSupported,
+    #[error("Ledger reply without state proof")]
+    StateProofMissing,
+    #[error("Unexpected ledger data")]
+    UnexpectedLedgerData,
+}
+
+impl DidIndyError {
+    /// Maps the error to the corresponding DID Resolution error code.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            DidIndyError::InvalidDidUrl
+            | DidIndyError::QueryParameterNotSupported
+            | DidIndyError::DateTimeError(_)
+            | DidIndyError::ObjectFamilyNotSupported
+            | DidIndyError::VersionNotSupported
+            | DidIndyError::ObjectTypeNotSuported => INVALID_DID,
+            // The indy method is supported, only the network is unknown
+            DidIndyError::NotFound
+            | DidIndyError::EmptyData
+            | DidIndyError::NamespaceNotSupported => NOT_FOUND,
+            DidIndyError::MethodNotSupported => METHOD_NOT_SUPPORTED,
+            DidIndyError::RepresentationNotSupported => REPRESENTATION_NOT_SUPPORTED,
+            DidIndyError::ParsingError(_)
+            | DidIndyError::InvalidDidDoc
+            | DidIndyError::NotImplemented
+            | DidIndyError::VdrError(_)
+            | DidIndyError::FromBase58Error(_)
+            | DidIndyError::UnexpectedKeyFormat
+            | DidIndyError::StateProofMissing
+            | DidIndyError::UnexpectedLedgerData => INTERNAL_ERROR,
+        }
+    }
 }
 
 // impl fmt::Display for DidIndyError {
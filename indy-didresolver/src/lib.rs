@@ -1,5 +1,9 @@
+pub mod anoncreds;
+mod cache;
+pub mod conversion;
 pub mod did;
 pub mod did_document;
+pub mod diff;
 pub mod error;
 pub mod resolver;
 pub mod responses;
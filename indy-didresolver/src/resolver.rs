@@ -1,145 +1,989 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use std::time::Duration;
 
 use futures_executor::block_on;
-use indy_vdr::utils::Qualifiable;
+use futures_util::future::{join, join_all};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use super::did::{DidUrl, LedgerObject, QueryParameter};
-use super::did_document::{DidDocument, LEGACY_INDY_SERVICE};
+use super::anoncreds;
+use super::cache::{Cache, Expiry};
+use super::conversion;
+use super::did::{DidUrl, LedgerObject, QueryParameter, RevReg, REVOCATION_STATUS_LIST};
+use super::did_document::{
+    find_by_fragment, parse_diddoc_content, service_endpoint, DidDocument, KeyRepresentation,
+    LEGACY_INDY_SERVICE,
+};
+use super::diff::{diff, DidDocumentDiff, Version};
 use super::error::{DidIndyError, DidIndyResult};
-use super::responses::{Endpoint, GetNymResultV1};
+use super::responses::{Endpoint, GetNymResultV1, GetSchemaResult};
 
 use indy_vdr::common::error::VdrResult;
 use indy_vdr::ledger::constants;
-use indy_vdr::ledger::identifiers::{CredentialDefinitionId, RevocationRegistryId, SchemaId};
 use indy_vdr::ledger::RequestBuilder;
 use indy_vdr::pool::helpers::perform_ledger_request;
 use indy_vdr::pool::{Pool, PreparedRequest, RequestResult, TimingResult};
 use indy_vdr::utils::did::DidValue;
 use log::error;
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub const DID_JSON: &str = "application/did+json";
+pub const DID_LD_JSON: &str = "application/did+ld+json";
+pub const JSON: &str = "application/json";
+pub const TEXT_URI_LIST: &str = "text/uri-list";
+// Ledger holding NYMs and AnonCreds objects, used for GET_TXN
+const DOMAIN_LEDGER_ID: i32 = 1;
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub enum Result {
     DidDocument(DidDocument),
     Content(Value),
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// Whether the ledger reply a result is based on carries a BLS multi-signed state proof.
+/// indy-vdr falls back to f+1 matching replies if a state proof fails to verify, so a present
+/// state proof is no guarantee that it was verified.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum StateProof {
+    Present,
+    Missing,
+}
+
+/// Ledger role of a NYM.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NymRole {
+    Trustee,
+    Steward,
+    Endorser,
+    NetworkMonitor,
+    None,
+}
+
+impl NymRole {
+    /// Maps the role code of a NYM transaction, `None` for unknown codes.
+    pub fn from_code(code: Option<&str>) -> Option<NymRole> {
+        match code {
+            Some("0") => Some(NymRole::Trustee),
+            Some("2") => Some(NymRole::Steward),
+            Some("101") => Some(NymRole::Endorser),
+            Some("201") => Some(NymRole::NetworkMonitor),
+            None | Some("") => Some(NymRole::None),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Metadata about the ledger object a DID or DID URL was resolved to.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentMetadata {
     node_response: Value,
     object_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_update: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_version_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deactivated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_proof: Option<StateProof>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    legacy_id: Option<String>,
+    #[serde(rename = "indy:role", skip_serializing_if = "Option::is_none")]
+    role: Option<NymRole>,
+    #[serde(rename = "indy:creator", skip_serializing_if = "Option::is_none")]
+    creator: Option<String>,
+    #[serde(rename = "indy:endorser", skip_serializing_if = "Option::is_none")]
+    endorser: Option<String>,
 }
 
+impl ContentMetadata {
+    /// Raw reply of the ledger node(s).
+    pub fn node_response(&self) -> &Value {
+        &self.node_response
+    }
+
+    /// Type of the ledger object, e.g. `NYM` or `SCHEMA`.
+    pub fn object_type(&self) -> &str {
+        &self.object_type
+    }
+
+    /// Time of the first transaction writing the object.
+    pub fn created(&self) -> Option<&str> {
+        self.created.as_deref()
+    }
+
+    /// Time of the transaction writing the resolved version of a NYM.
+    pub fn updated(&self) -> Option<&str> {
+        self.updated.as_deref()
+    }
+
+    /// Sequence number of the transaction writing the resolved version.
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    pub fn version_time(&self) -> Option<&str> {
+        self.version_time.as_deref()
+    }
+
+    /// Time of the update following a historical NYM version.
+    pub fn next_update(&self) -> Option<&str> {
+        self.next_update.as_deref()
+    }
+
+    pub fn next_version_id(&self) -> Option<&str> {
+        self.next_version_id.as_deref()
+    }
+
+    /// Whether the resolved NYM has been deactivated by setting its verkey to null.
+    pub fn deactivated(&self) -> bool {
+        self.deactivated.unwrap_or(false)
+    }
+
+    pub fn state_proof(&self) -> Option<StateProof> {
+        self.state_proof
+    }
+
+    /// Legacy Indy identifier of an AnonCreds object, e.g. `<did>:2:<name>:<version>`.
+    pub fn legacy_id(&self) -> Option<&str> {
+        self.legacy_id.as_deref()
+    }
+
+    /// Role of the resolved NYM.
+    pub fn role(&self) -> Option<NymRole> {
+        self.role
+    }
+
+    /// DID that created the NYM. indy-node keeps it when the NYM is updated, so it may differ
+    /// from the submitter of the resolved NYM version.
+    pub fn creator(&self) -> Option<&str> {
+        self.creator.as_deref()
+    }
+
+    /// DID that endorsed the transaction writing the resolved NYM version.
+    pub fn endorser(&self) -> Option<&str> {
+        self.endorser.as_deref()
+    }
+}
+
+/// DID resolution or dereferencing metadata, `error` is set if the request failed.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ResolutionMetadata {
+    fn new(content_type: &str) -> Self {
+        ResolutionMetadata {
+            content_type: Some(content_type.to_string()),
+            error: None,
+        }
+    }
+
+    fn from_error(content_type: &str, error: &DidIndyError) -> Self {
+        ResolutionMetadata {
+            content_type: Some(content_type.to_string()),
+            error: Some(error.error_code().to_string()),
+        }
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// DID Resolution error code, e.g. `notFound`.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Result of resolving a DID, as defined by DID Resolution.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ResolutionResult {
-    did_resolution_metadata: Option<String>,
+    did_resolution_metadata: ResolutionMetadata,
     did_document: Option<Value>,
     did_document_metadata: Option<ContentMetadata>,
 }
 
+impl ResolutionResult {
+    /// Builds the result for a failed resolution.
+    pub fn from_error(error: &DidIndyError) -> Self {
+        ResolutionResult {
+            did_resolution_metadata: ResolutionMetadata::from_error(DID_JSON, error),
+            did_document: None,
+            did_document_metadata: None,
+        }
+    }
+
+    pub fn did_resolution_metadata(&self) -> &ResolutionMetadata {
+        &self.did_resolution_metadata
+    }
+
+    /// The resolved DID document in its JSON representation.
+    pub fn did_document(&self) -> Option<&Value> {
+        self.did_document.as_ref()
+    }
+
+    pub fn did_document_metadata(&self) -> Option<&ContentMetadata> {
+        self.did_document_metadata.as_ref()
+    }
+}
+
+/// A NYM version in the history of a DID.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DidDocumentVersion {
+    version_id: String,
+    version_time: Option<String>,
+    did_document: Value,
+}
+
+impl DidDocumentVersion {
+    /// Sequence number of the NYM transaction writing the version.
+    pub fn version_id(&self) -> &str {
+        &self.version_id
+    }
+
+    /// Time of the NYM transaction writing the version.
+    pub fn version_time(&self) -> Option<&str> {
+        self.version_time.as_deref()
+    }
+
+    pub fn did_document(&self) -> &Value {
+        &self.did_document
+    }
+}
+
+/// Result of dereferencing a DID URL, as defined by DID Resolution.
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DereferencingResult {
-    dereferencing_metadata: Option<String>,
+    dereferencing_metadata: ResolutionMetadata,
     content_stream: Option<Value>,
     content_metadata: Option<ContentMetadata>,
 }
 
+impl DereferencingResult {
+    /// Builds the result for a failed dereferencing.
+    pub fn from_error(error: &DidIndyError) -> Self {
+        DereferencingResult {
+            dereferencing_metadata: ResolutionMetadata::from_error(JSON, error),
+            content_stream: None,
+            content_metadata: None,
+        }
+    }
+
+    pub fn dereferencing_metadata(&self) -> &ResolutionMetadata {
+        &self.dereferencing_metadata
+    }
+
+    /// The dereferenced ledger object, e.g. a schema or credential definition.
+    pub fn content_stream(&self) -> Option<&Value> {
+        self.content_stream.as_ref()
+    }
+
+    pub fn content_metadata(&self) -> Option<&ContentMetadata> {
+        self.content_metadata.as_ref()
+    }
+}
+
 pub struct Resolver<T: Pool> {
     pool: T,
+    require_state_proof_present: bool,
+    cache: Option<Cache<(Result, ContentMetadata)>>,
+    ttl: Duration,
+    nym_version_metadata: bool,
+    endorser_metadata: bool,
 }
 
 impl<T: Pool> Resolver<T> {
     pub fn new(pool: T) -> Resolver<T> {
-        Resolver { pool }
+        Resolver {
+            pool,
+            require_state_proof_present: false,
+            cache: None,
+            ttl: Duration::from_secs(0),
+            nym_version_metadata: false,
+            endorser_metadata: false,
+        }
     }
 
-    pub fn dereference(&self, did_url: &str) -> DidIndyResult<String> {
-        let (data, metadata) = self._resolve(did_url)?;
+    /// Caches up to `capacity` results. SCHEMA and TXN objects and REV_REG_ENTRY objects at
+    /// a past versionTime never change and are cached until evicted. NYMs, CLAIM_DEF and
+    /// REV_REG_DEF objects can be updated and expire after `ttl`.
+    pub fn enable_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some(Cache::new(capacity));
+        self.ttl = ttl;
+        self
+    }
 
-        let content = match data {
-            Result::Content(c) => Some(c),
-            _ => None,
+    /// Adds `created` to the metadata of NYMs and `nextUpdate` and `nextVersionId` to the
+    /// metadata of historical NYM versions. They are found by walking through the NYM versions,
+    /// which takes one ledger request per version.
+    pub fn enable_nym_version_metadata(mut self) -> Self {
+        self.nym_version_metadata = true;
+        self
+    }
+
+    /// Adds `indy:endorser` to the metadata of NYMs. The endorser is only part of the written
+    /// transaction, which takes one more ledger request per NYM.
+    pub fn enable_endorser_metadata(mut self) -> Self {
+        self.endorser_metadata = true;
+        self
+    }
+
+    /// Rejects every ledger reply without state proof. GET_TXN replies and NYM versions
+    /// requested by versionId never carry one, so TXN and versionId DID URLs as well as
+    /// credential definitions, which need the schema transaction, cannot be resolved and the
+    /// endorser is left out. The state proof is not verified, only its presence is checked,
+    /// see [`StateProof`].
+    pub fn require_state_proof_present(mut self, require_state_proof_present: bool) -> Self {
+        self.require_state_proof_present = require_state_proof_present;
+        self
+    }
+
+    pub async fn dereference(&self, did_url: &str) -> DidIndyResult<DereferencingResult> {
+        self.dereference_with_keys(did_url, KeyRepresentation::default())
+            .await
+    }
+
+    /// Dereferences a DID URL, verification methods of DID documents are returned in the
+    /// given representation, see the `transformKeys` resolution option.
+    pub async fn dereference_with_keys(
+        &self,
+        did_url: &str,
+        keys: KeyRepresentation,
+    ) -> DidIndyResult<DereferencingResult> {
+        let parsed = DidUrl::from_str(did_url)?;
+        let (data, metadata) = self._resolve(parsed.without_fragment()).await?;
+
+        let (content, content_type) = match data {
+            Result::Content(_) if parsed.fragment.is_some() => return Err(DidIndyError::NotFound),
+            Result::Content(c) => (c, JSON),
+            Result::DidDocument(doc) => {
+                dereference_did_document(doc.to_value_with_keys(keys)?, &parsed)?
+            }
         };
 
         let result = DereferencingResult {
-            dereferencing_metadata: None,
-            content_stream: content,
+            dereferencing_metadata: ResolutionMetadata::new(content_type),
+            content_stream: Some(content),
             content_metadata: Some(metadata),
         };
 
-        Ok(serde_json::to_string_pretty(&result).unwrap())
+        Ok(result)
     }
 
-    pub fn resolve(&self, did: &str) -> DidIndyResult<String> {
-        let (data, metadata) = self._resolve(did)?;
+    /// Dereferences a legacy Indy identifier of a schema, credential definition or revocation
+    /// registry like the corresponding did:indy DID URL in `namespace`.
+    pub async fn dereference_legacy(
+        &self,
+        namespace: &str,
+        legacy_id: &str,
+    ) -> DidIndyResult<DereferencingResult> {
+        let did_url = conversion::to_did_url(namespace, legacy_id)?;
+        self.dereference(&did_url).await
+    }
+
+    /// Dereferences the credential definition `tag` of `issuer` for the schema at
+    /// `schema_did_url`, e.g. `did:indy:<namespace>:<did>/anoncreds/v0/SCHEMA/<name>/<version>`.
+    /// The sequence number of the schema is looked up on the ledger.
+    pub async fn dereference_cred_def_by_schema(
+        &self,
+        issuer: &str,
+        schema_did_url: &str,
+        tag: &str,
+    ) -> DidIndyResult<DereferencingResult> {
+        let issuer = DidUrl::from_str(issuer)?;
+        check_schema_did_url(&issuer, &DidUrl::from_str(schema_did_url)?)?;
+
+        let (_, metadata) = self._resolve(schema_did_url).await?;
+        let schema_seq_no = metadata
+            .version_id()
+            .and_then(|seq_no| seq_no.parse::<u32>().ok())
+            .ok_or(DidIndyError::NotFound)?;
+
+        let cred_def_did_url =
+            conversion::cred_def_did_url(&issuer.namespace, &issuer.id, schema_seq_no, tag);
+        self.dereference(&cred_def_did_url).await
+    }
+
+    /// Returns all versions of a DID document, oldest first.
+    pub async fn resolve_history(&self, did: &str) -> DidIndyResult<Vec<DidDocumentVersion>> {
+        let did_url = DidUrl::from_str(did)?;
+        if did_url.path.is_some() || !did_url.query.is_empty() || did_url.fragment.is_some() {
+            return Err(DidIndyError::InvalidDidUrl);
+        }
+
+        let versions = self.fetch_nym_versions(&did_url.id).await?;
+        if versions.is_empty() {
+            return Err(DidIndyError::NotFound);
+        }
+
+        // The documents are built from the GET_NYM replies of the walk, the versionId only
+        // selects the legacy endpoint at the time of the version
+        let version_urls = versions
+            .iter()
+            .map(|(seq_no, _, _)| {
+                DidUrl::from_str(&format!("{}?versionId={}", did_url.did(), seq_no))
+            })
+            .collect::<DidIndyResult<Vec<DidUrl>>>()?;
+        let documents = join_all(versions.iter().zip(&version_urls).map(
+            |((_, _, ledger_data), version_url)| async move {
+                let data = parse_ledger_data(ledger_data)?;
+                let get_nym_result: GetNymResultV1 =
+                    serde_json::from_str(data.as_str().ok_or(DidIndyError::UnexpectedLedgerData)?)?;
+                self.build_did_document(version_url, ledger_data, &get_nym_result)
+                    .await
+            },
+        ))
+        .await;
+
+        versions
+            .into_iter()
+            .zip(documents)
+            .map(|((seq_no, txn_time, _), doc)| {
+                Ok(DidDocumentVersion {
+                    version_id: seq_no.to_string(),
+                    version_time: format_timestamp(txn_time),
+                    did_document: doc?.to_value()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Compares two versions of a DID document.
+    pub async fn diff_versions(
+        &self,
+        did: &str,
+        from: &Version,
+        to: &Version,
+    ) -> DidIndyResult<DidDocumentDiff> {
+        let did_url = DidUrl::from_str(did)?;
+        if did_url.path.is_some() || !did_url.query.is_empty() || did_url.fragment.is_some() {
+            return Err(DidIndyError::InvalidDidUrl);
+        }
+
+        let from_url = format!("{}?{}", did_url.did(), from.to_query());
+        let to_url = format!("{}?{}", did_url.did(), to.to_query());
+        let (from_doc, to_doc) = join(self.resolve(&from_url), self.resolve(&to_url)).await;
+        match (from_doc?.did_document, to_doc?.did_document) {
+            (Some(from_doc), Some(to_doc)) => Ok(diff(&from_doc, &to_doc)),
+            _ => Err(DidIndyError::NotFound),
+        }
+    }
+
+    /// Dereferences all DID URLs concurrently over the pool. The results are returned in the
+    /// order of `did_urls`.
+    pub async fn dereference_all(
+        &self,
+        did_urls: &[&str],
+    ) -> Vec<DidIndyResult<DereferencingResult>> {
+        join_all(did_urls.iter().map(|did_url| self.dereference(did_url))).await
+    }
+
+    pub async fn resolve(&self, did: &str) -> DidIndyResult<ResolutionResult> {
+        self.resolve_with_keys(did, KeyRepresentation::default())
+            .await
+    }
+
+    /// Resolves a DID with the verkey in the given representation, see the `transformKeys`
+    /// resolution option.
+    pub async fn resolve_with_keys(
+        &self,
+        did: &str,
+        keys: KeyRepresentation,
+    ) -> DidIndyResult<ResolutionResult> {
+        let (data, metadata) = self._resolve(did).await?;
 
         let diddoc = match data {
-            Result::DidDocument(doc) => Some(doc.to_value()?),
+            Result::DidDocument(doc) => Some(doc.to_value_with_keys(keys)?),
             _ => None,
         };
+        let content_type = match &diddoc {
+            Some(doc) => did_document_content_type(doc),
+            None => DID_JSON,
+        };
         let result = ResolutionResult {
-            did_resolution_metadata: None,
+            did_resolution_metadata: ResolutionMetadata::new(content_type),
             did_document: diddoc,
             did_document_metadata: Some(metadata),
         };
 
-        Ok(serde_json::to_string_pretty(&result).unwrap())
+        Ok(result)
+    }
+
+    // Blocking variants for callers without an async runtime, e.g. the driver
+    pub fn dereference_blocking(&self, did_url: &str) -> DidIndyResult<DereferencingResult> {
+        block_on(self.dereference(did_url))
+    }
+
+    pub fn dereference_with_keys_blocking(
+        &self,
+        did_url: &str,
+        keys: KeyRepresentation,
+    ) -> DidIndyResult<DereferencingResult> {
+        block_on(self.dereference_with_keys(did_url, keys))
+    }
+
+    pub fn dereference_legacy_blocking(
+        &self,
+        namespace: &str,
+        legacy_id: &str,
+    ) -> DidIndyResult<DereferencingResult> {
+        block_on(self.dereference_legacy(namespace, legacy_id))
+    }
+
+    pub fn dereference_cred_def_by_schema_blocking(
+        &self,
+        issuer: &str,
+        schema_did_url: &str,
+        tag: &str,
+    ) -> DidIndyResult<DereferencingResult> {
+        block_on(self.dereference_cred_def_by_schema(issuer, schema_did_url, tag))
+    }
+
+    pub fn resolve_history_blocking(&self, did: &str) -> DidIndyResult<Vec<DidDocumentVersion>> {
+        block_on(self.resolve_history(did))
+    }
+
+    pub fn diff_versions_blocking(
+        &self,
+        did: &str,
+        from: &Version,
+        to: &Version,
+    ) -> DidIndyResult<DidDocumentDiff> {
+        block_on(self.diff_versions(did, from, to))
+    }
+
+    pub fn resolve_blocking(&self, did: &str) -> DidIndyResult<ResolutionResult> {
+        block_on(self.resolve(did))
+    }
+
+    pub fn resolve_with_keys_blocking(
+        &self,
+        did: &str,
+        keys: KeyRepresentation,
+    ) -> DidIndyResult<ResolutionResult> {
+        block_on(self.resolve_with_keys(did, keys))
+    }
+
+    pub fn dereference_all_blocking(
+        &self,
+        did_urls: &[&str],
+    ) -> Vec<DidIndyResult<DereferencingResult>> {
+        block_on(self.dereference_all(did_urls))
     }
 
-    fn _resolve(&self, did: &str) -> DidIndyResult<(Result, ContentMetadata)> {
+    async fn _resolve(&self, did: &str) -> DidIndyResult<(Result, ContentMetadata)> {
         let did_url = DidUrl::from_str(did)?;
 
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.resolve_from_ledger(&did_url).await,
+        };
+        if let Some(cached) = cache.get(did) {
+            return Ok(cached);
+        }
+
+        let resolved = self.resolve_from_ledger(&did_url).await?;
+        if let Some(expiry) = cache_expiry(&did_url, self.ttl) {
+            cache.insert(did, resolved.clone(), expiry);
+        }
+        Ok(resolved)
+    }
+
+    async fn resolve_from_ledger(
+        &self,
+        did_url: &DidUrl,
+    ) -> DidIndyResult<(Result, ContentMetadata)> {
+        if let Some(rev_reg) = status_list_request(did_url)? {
+            return self.resolve_status_list(did_url, &rev_reg).await;
+        }
+
         let builder = self.pool.get_request_builder();
-        let request = build_request(&did_url, &builder)?;
+        let request = build_request(did_url, &builder)?;
 
-        let ledger_data = handle_request(&self.pool, &request)?;
+        let (ledger_data, state_proof) = self.request_ledger(&request).await?;
         let data = parse_ledger_data(&ledger_data)?;
 
+        let (version_id, txn_time) = parse_version(&ledger_data);
+        let mut legacy_id = None;
+        let mut nym = None;
+
         let (result, object_type) = match request.txn_type.as_str() {
             constants::GET_NYM => {
                 let get_nym_result: GetNymResultV1 = serde_json::from_str(data.as_str().unwrap())?;
 
-                let endpoint: Option<Endpoint> = if get_nym_result.diddoc_content.is_none() {
-                    // Legacy: Try to find an attached ATTRIBUTE transacation with raw endpoint
-                    self.fetch_legacy_endpoint(&did_url.id).ok()
-                } else {
-                    None
-                };
-
-                let did_document = DidDocument::new(
-                    &did_url.namespace,
-                    &get_nym_result.dest,
-                    &get_nym_result.verkey,
-                    endpoint,
-                    None,
-                );
+                let did_document = self
+                    .build_did_document(did_url, &ledger_data, &get_nym_result)
+                    .await?;
+                nym = Some(get_nym_result);
                 (Result::DidDocument(did_document), String::from("NYM"))
             }
-            constants::GET_CRED_DEF => (Result::Content(data), String::from("CRED_DEF")),
-            constants::GET_SCHEMA => (Result::Content(data), String::from("SCHEMA")),
-            constants::GET_REVOC_REG_DEF => (Result::Content(data), String::from("REVOC_REG_DEF")),
+            constants::GET_CRED_DEF => {
+                let reply: Value = serde_json::from_str(&ledger_data)?;
+                let schema_seq_no = reply["result"]["ref"]
+                    .as_i64()
+                    .ok_or(DidIndyError::UnexpectedLedgerData)?;
+                let schema_id = self.fetch_schema_id(did_url, schema_seq_no).await?;
+                let cred_def = anoncreds::CredentialDefinition::from_ledger(
+                    &did_url.did(),
+                    &schema_id,
+                    &reply["result"],
+                )?;
+                legacy_id = Some(conversion::to_legacy_id(did_url)?.to_string());
+                (
+                    Result::Content(serde_json::to_value(cred_def)?),
+                    String::from("CRED_DEF"),
+                )
+            }
+            constants::GET_SCHEMA => {
+                // Unknown schemas are answered with name and version only
+                if version_id.is_none() {
+                    return Err(DidIndyError::NotFound);
+                }
+                let get_schema_result: GetSchemaResult = serde_json::from_value(data)?;
+                legacy_id = Some(conversion::to_legacy_id(did_url)?.to_string());
+                let schema = anoncreds::Schema::from_ledger(&did_url.did(), get_schema_result);
+                (
+                    Result::Content(serde_json::to_value(schema)?),
+                    String::from("SCHEMA"),
+                )
+            }
+            constants::GET_REVOC_REG_DEF => {
+                legacy_id = data["id"].as_str().map(String::from);
+                let rev_reg_def = anoncreds::RevocationRegistryDefinition::from_ledger(
+                    &did_url.namespace,
+                    &did_url.did(),
+                    data,
+                )?;
+                (
+                    Result::Content(serde_json::to_value(rev_reg_def)?),
+                    String::from("REVOC_REG_DEF"),
+                )
+            }
             constants::GET_REVOC_REG_DELTA => {
                 (Result::Content(data), String::from("REVOC_REG_DELTA"))
             }
+            constants::GET_TXN => {
+                // Only transactions written by the DID of the DID URL can be dereferenced
+                if data["txn"]["metadata"]["from"].as_str() != Some(&did_url.id) {
+                    return Err(DidIndyError::NotFound);
+                }
+                (Result::Content(data), String::from("TXN"))
+            }
             _ => (Result::Content(data), String::from("UNKOWN")),
         };
 
-        let metadata = ContentMetadata {
+        let mut metadata = ContentMetadata {
             node_response: serde_json::from_str(&ledger_data).unwrap(),
             object_type,
+            version_id: version_id.map(|v| v.to_string()),
+            version_time: txn_time.and_then(format_timestamp),
+            state_proof: Some(state_proof),
+            legacy_id,
+            ..Default::default()
         };
 
+        if let Result::DidDocument(did_document) = &result {
+            if did_document.is_deactivated() {
+                metadata.deactivated = Some(true);
+            }
+        }
+
+        if let Some(nym) = nym {
+            metadata.role = NymRole::from_code(nym.role.as_deref());
+            metadata.creator = nym
+                .identifier
+                .map(|creator| qualify_did(&did_url.namespace, &creator));
+            if let (Some(seq_no), Some(txn_time)) = (version_id, txn_time) {
+                self.add_nym_metadata(did_url, seq_no, txn_time, &mut metadata)
+                    .await;
+            }
+        } else if matches!(
+            request.txn_type.as_str(),
+            constants::GET_SCHEMA | constants::GET_TXN
+        ) {
+            // Schemas and transactions are written once, other objects can be updated
+            metadata.created = metadata.version_time.clone();
+        }
+
         let result_with_metadata = (result, metadata);
 
         Ok(result_with_metadata)
     }
 
-    fn fetch_legacy_endpoint(&self, did: &DidValue) -> DidIndyResult<Endpoint> {
+    // Builds the revocation status list by replaying all issuance and revocation deltas
+    // from the creation of the registry up to versionTime
+    async fn resolve_status_list(
+        &self,
+        did_url: &DidUrl,
+        rev_reg: &RevReg,
+    ) -> DidIndyResult<(Result, ContentMetadata)> {
+        let builder = self.pool.get_request_builder();
+        let id = conversion::rev_reg_id(&did_url.id, rev_reg);
+        let timestamp = parse_or_now(did_url.query.get(&QueryParameter::VersionTime))?;
+
+        let request = builder.build_get_revoc_reg_def_request(None, &id)?;
+        let (ledger_data, _) = self.request_ledger(&request).await?;
+        let rev_reg_def = parse_ledger_data(&ledger_data)?;
+
+        let request = builder.build_get_revoc_reg_delta_request(None, &id, None, timestamp)?;
+        let (ledger_data, state_proof) = self.request_ledger(&request).await?;
+        let delta = parse_ledger_data(&ledger_data)?;
+
+        let status_list = anoncreds::RevocationStatusList::from_ledger(
+            &did_url.did(),
+            &conversion::rev_reg_def_did_url(
+                &did_url.namespace,
+                &did_url.id,
+                rev_reg.schema_seq_no,
+                &rev_reg.claim_def_name,
+                &rev_reg.tag,
+            ),
+            &rev_reg_def,
+            &delta,
+        )?;
+
+        let metadata = ContentMetadata {
+            node_response: serde_json::from_str(&ledger_data)?,
+            object_type: String::from("REV_STATUS_LIST"),
+            version_time: format_timestamp(status_list.timestamp),
+            state_proof: Some(state_proof),
+            legacy_id: Some(id.to_string()),
+            ..Default::default()
+        };
+        Ok((
+            Result::Content(serde_json::to_value(status_list)?),
+            metadata,
+        ))
+    }
+
+    async fn request_ledger(
+        &self,
+        request: &PreparedRequest,
+    ) -> DidIndyResult<(String, StateProof)> {
+        let ledger_data = handle_request(&self.pool, request).await?;
+        let state_proof = parse_state_proof(&ledger_data);
+        if self.require_state_proof_present && state_proof != StateProof::Present {
+            error!("Ledger reply without state proof rejected");
+            return Err(DidIndyError::StateProofMissing);
+        }
+        Ok((ledger_data, state_proof))
+    }
+
+    async fn build_did_document(
+        &self,
+        did_url: &DidUrl,
+        ledger_data: &str,
+        get_nym_result: &GetNymResultV1,
+    ) -> DidIndyResult<DidDocument> {
+        let verkey = match &get_nym_result.verkey {
+            Some(verkey) => verkey,
+            None => {
+                return Ok(DidDocument::new_deactivated(
+                    &did_url.namespace,
+                    &get_nym_result.dest,
+                ))
+            }
+        };
+
+        // Invalid diddocContent is an error, not a reason to fall back to legacy endpoints
+        let diddoc_content = match &get_nym_result.diddoc_content {
+            Some(content) => Some(parse_diddoc_content(content)?),
+            None => None,
+        };
+
+        let endpoint: Option<Endpoint> = if diddoc_content.is_none() {
+            // Legacy: Try to find an attached ATTRIBUTE transacation with raw endpoint.
+            // For historical versions the endpoint is read as it was at that time.
+            let (seq_no, timestamp) = parse_nym_version(did_url)?;
+            let timestamp = if seq_no.is_some() {
+                parse_version(ledger_data).1
+            } else {
+                timestamp
+            };
+            self.fetch_legacy_endpoint(&did_url.id, timestamp)
+                .await
+                .ok()
+        } else {
+            None
+        };
+
+        Ok(DidDocument::new(
+            &did_url.namespace,
+            &get_nym_result.dest,
+            verkey,
+            endpoint,
+            diddoc_content,
+        ))
+    }
+
+    // Lookups of other NYM versions are best effort, metadata is left out if they fail
+    async fn add_nym_metadata(
+        &self,
+        did_url: &DidUrl,
+        seq_no: u64,
+        txn_time: i64,
+        metadata: &mut ContentMetadata,
+    ) {
+        metadata.updated = format_timestamp(txn_time);
+        if self.endorser_metadata {
+            metadata.endorser = self
+                .fetch_endorser(seq_no)
+                .await
+                .ok()
+                .flatten()
+                .map(|endorser| qualify_did(&did_url.namespace, &endorser));
+        }
+        if !self.nym_version_metadata {
+            return;
+        }
+        metadata.created = self
+            .fetch_nym_created(&did_url.id, txn_time)
+            .await
+            .ok()
+            .and_then(format_timestamp);
+
+        if did_url.query.contains_key(&QueryParameter::VersionId)
+            || did_url.query.contains_key(&QueryParameter::VersionTime)
+        {
+            if let Ok(Some((next_seq_no, next_txn_time))) =
+                self.fetch_next_nym_version(&did_url.id, txn_time).await
+            {
+                metadata.next_version_id = Some(next_seq_no.to_string());
+                metadata.next_update = format_timestamp(next_txn_time);
+            }
+        }
+    }
+
+    // The endorser is only part of the written transaction, not of the GET_NYM reply
+    async fn fetch_endorser(&self, seq_no: u64) -> DidIndyResult<Option<String>> {
+        let builder = self.pool.get_request_builder();
+        let seq_no = i32::try_from(seq_no).map_err(|_| DidIndyError::UnexpectedLedgerData)?;
+        let request = builder.build_get_txn_request(None, DOMAIN_LEDGER_ID, seq_no)?;
+        let (ledger_data, _) = self.request_ledger(&request).await?;
+        let txn = parse_ledger_data(&ledger_data)?;
+        Ok(txn["txn"]["metadata"]["endorser"]
+            .as_str()
+            .map(String::from))
+    }
+
+    // Returns seqNo and txnTime of the NYM version valid at the given time, None if the NYM
+    // did not exist yet
+    async fn fetch_nym_version_at(
+        &self,
+        did: &DidValue,
+        timestamp: Option<i64>,
+    ) -> DidIndyResult<Option<(u64, i64)>> {
+        Ok(self
+            .fetch_nym_reply_at(did, timestamp)
+            .await?
+            .map(|(seq_no, txn_time, _)| (seq_no, txn_time)))
+    }
+
+    // Like fetch_nym_version_at, but also returns the GET_NYM reply
+    async fn fetch_nym_reply_at(
+        &self,
+        did: &DidValue,
+        timestamp: Option<i64>,
+    ) -> DidIndyResult<Option<(u64, i64, String)>> {
+        let builder = self.pool.get_request_builder();
+        let request = with_version(builder.build_get_nym_request(None, did)?, None, timestamp);
+        let (ledger_data, _) = self.request_ledger(&request).await?;
+        match parse_ledger_data(&ledger_data) {
+            Ok(_) => match parse_version(&ledger_data) {
+                (Some(seq_no), Some(txn_time)) => Ok(Some((seq_no, txn_time, ledger_data))),
+                _ => Err(DidIndyError::EmptyData),
+            },
+            Err(DidIndyError::EmptyData) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Walks back through the NYM versions preceding the one written at txn_time.
+    // Versions written within the same second cannot be told apart by time.
+    async fn fetch_nym_created(&self, did: &DidValue, txn_time: i64) -> DidIndyResult<i64> {
+        let mut created = txn_time;
+        while let Some((_, previous_txn_time)) =
+            self.fetch_nym_version_at(did, Some(created - 1)).await?
+        {
+            if previous_txn_time >= created {
+                break;
+            }
+            created = previous_txn_time;
+        }
+        Ok(created)
+    }
+
+    // Walks back from the current NYM version with one GET_NYM per version, returns seqNo,
+    // txnTime and the reply of all versions oldest first. Each step must reach an earlier
+    // transaction, versions written within the same second cannot be told apart by time.
+    async fn fetch_nym_versions(&self, did: &DidValue) -> DidIndyResult<Vec<(u64, i64, String)>> {
+        let mut versions: Vec<(u64, i64, String)> = Vec::new();
+        let mut version = self.fetch_nym_reply_at(did, None).await?;
+        while let Some((seq_no, txn_time, ledger_data)) = version {
+            if matches!(versions.last(), Some((later_seq_no, _, _)) if seq_no >= *later_seq_no) {
+                break;
+            }
+            versions.push((seq_no, txn_time, ledger_data));
+            version = self.fetch_nym_reply_at(did, Some(txn_time - 1)).await?;
+        }
+        versions.reverse();
+        Ok(versions)
+    }
+
+    // Walks back from the current NYM version to the one directly following txn_time
+    async fn fetch_next_nym_version(
+        &self,
+        did: &DidValue,
+        txn_time: i64,
+    ) -> DidIndyResult<Option<(u64, i64)>> {
+        let mut next = None;
+        let mut version = self.fetch_nym_version_at(did, None).await?;
+        while let Some((seq_no, version_txn_time)) = version {
+            if version_txn_time <= txn_time {
+                break;
+            }
+            next = Some((seq_no, version_txn_time));
+            version = self
+                .fetch_nym_version_at(did, Some(version_txn_time - 1))
+                .await?;
+        }
+        Ok(next)
+    }
+
+    // Resolves the did:indy DID URL of the schema written in transaction seq_no
+    async fn fetch_schema_id(&self, did_url: &DidUrl, seq_no: i64) -> DidIndyResult<String> {
+        let builder = self.pool.get_request_builder();
+        let seq_no = i32::try_from(seq_no).map_err(|_| DidIndyError::UnexpectedLedgerData)?;
+        let request = builder.build_get_txn_request(None, DOMAIN_LEDGER_ID, seq_no)?;
+        let (ledger_data, _) = self.request_ledger(&request).await?;
+        parse_schema_txn(&did_url.namespace, &parse_ledger_data(&ledger_data)?)
+    }
+
+    async fn fetch_legacy_endpoint(
+        &self,
+        did: &DidValue,
+        timestamp: Option<i64>,
+    ) -> DidIndyResult<Endpoint> {
         let builder = self.pool.get_request_builder();
         let request = builder.build_get_attrib_request(
             None,
@@ -148,45 +992,61 @@ impl<T: Pool> Resolver<T> {
             None,
             None,
         )?;
-        let ledger_data = handle_request(&self.pool, &request)?;
+        let request = with_version(request, None, timestamp);
+        let (ledger_data, _) = self.request_ledger(&request).await?;
         let endpoint_data = parse_ledger_data(&ledger_data)?;
         let endpoint_data: Endpoint = serde_json::from_str(endpoint_data.as_str().unwrap())?;
         Ok(endpoint_data)
     }
 }
 
+// The issuer has to be a DID and the schema has to be on the same network
+fn check_schema_did_url(issuer: &DidUrl, schema: &DidUrl) -> DidIndyResult<()> {
+    if issuer.path.is_some() || issuer.namespace != schema.namespace {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+    match schema.path.as_deref().map(LedgerObject::from_str) {
+        Some(Ok(LedgerObject::Schema(_))) => Ok(()),
+        Some(Err(err)) => Err(err),
+        _ => Err(DidIndyError::InvalidDidUrl),
+    }
+}
+
+// Returns the revocation registry if a status list is requested via resourceType
+fn status_list_request(did: &DidUrl) -> DidIndyResult<Option<RevReg>> {
+    let resource_type = match did.query.get(&QueryParameter::ResourceType) {
+        Some(resource_type) => resource_type,
+        None => return Ok(None),
+    };
+    if resource_type != REVOCATION_STATUS_LIST {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+    let path = did
+        .path
+        .as_ref()
+        .ok_or(DidIndyError::QueryParameterNotSupported)?;
+    match LedgerObject::from_str(path)? {
+        LedgerObject::RevRegEntry(rev_reg)
+            if !did.query.contains_key(&QueryParameter::From)
+                && !did.query.contains_key(&QueryParameter::To) =>
+        {
+            Ok(Some(rev_reg))
+        }
+        _ => Err(DidIndyError::QueryParameterNotSupported),
+    }
+}
+
 fn build_request(did: &DidUrl, builder: &RequestBuilder) -> DidIndyResult<PreparedRequest> {
     let request = if did.path.is_some() {
         match LedgerObject::from_str(did.path.as_ref().unwrap().as_str())? {
-            LedgerObject::Schema(schema) => builder.build_get_schema_request(
-                None,
-                &SchemaId::new(&did.id, &schema.name, &schema.version),
-            ),
-            LedgerObject::ClaimDef(claim_def) => builder.build_get_cred_def_request(
-                None,
-                &CredentialDefinitionId::from_str(
-                    format!(
-                        "{}:3:CL:{}:{}",
-                        &did.id, claim_def.schema_seq_no, claim_def.name
-                    )
-                    .as_str(),
-                )
-                .unwrap(),
-            ),
+            LedgerObject::Schema(schema) => {
+                builder.build_get_schema_request(None, &conversion::schema_id(&did.id, &schema))
+            }
+            LedgerObject::ClaimDef(claim_def) => builder
+                .build_get_cred_def_request(None, &conversion::cred_def_id(&did.id, &claim_def)),
             LedgerObject::RevRegDef(rev_reg_def) => builder.build_get_revoc_reg_def_request(
                 None,
-                &RevocationRegistryId::from_str(
-                    format!(
-                        "{}:4:{}:3:CL:{}:{}:CL_ACCUM:{}",
-                        &did.id,
-                        &did.id,
-                        rev_reg_def.schema_seq_no,
-                        rev_reg_def.claim_def_name,
-                        rev_reg_def.tag
-                    )
-                    .as_str(),
-                )
-                .unwrap(),
+                &conversion::rev_reg_id(&did.id, &rev_reg_def),
             ),
             LedgerObject::RevRegEntry(rev_reg_entry) => {
                 // If From or To parameters, return RevRegDelta request
@@ -206,18 +1066,7 @@ fn build_request(did: &DidUrl, builder: &RequestBuilder) -> DidIndyResult<Prepar
 
                     builder.build_get_revoc_reg_delta_request(
                         None,
-                        &RevocationRegistryId::from_str(
-                            format!(
-                                "{}:4:{}:3:CL:{}:{}:CL_ACCUM:{}",
-                                &did.id,
-                                &did.id,
-                                rev_reg_entry.schema_seq_no,
-                                rev_reg_entry.claim_def_name,
-                                rev_reg_entry.tag
-                            )
-                            .as_str(),
-                        )
-                        .unwrap(),
+                        &conversion::rev_reg_id(&did.id, &rev_reg_entry),
                         from,
                         to,
                     )
@@ -227,18 +1076,7 @@ fn build_request(did: &DidUrl, builder: &RequestBuilder) -> DidIndyResult<Prepar
 
                     builder.build_get_revoc_reg_request(
                         None,
-                        &RevocationRegistryId::from_str(
-                            format!(
-                                "{}:4:{}:3:CL:{}:{}:CL_ACCUM:{}",
-                                &did.id,
-                                &did.id,
-                                rev_reg_entry.schema_seq_no,
-                                rev_reg_entry.claim_def_name,
-                                rev_reg_entry.tag
-                            )
-                            .as_str(),
-                        )
-                        .unwrap(),
+                        &conversion::rev_reg_id(&did.id, &rev_reg_entry),
                         timestamp,
                     )
                 }
@@ -258,43 +1096,66 @@ fn build_request(did: &DidUrl, builder: &RequestBuilder) -> DidIndyResult<Prepar
 
                 builder.build_get_revoc_reg_delta_request(
                     None,
-                    &RevocationRegistryId::from_str(
-                        format!(
-                            "{}:4:{}:3:CL:{}:{}:CL_ACCUM:{}",
-                            &did.id,
-                            &did.id,
-                            rev_reg_delta.schema_seq_no,
-                            rev_reg_delta.claim_def_name,
-                            rev_reg_delta.tag
-                        )
-                        .as_str(),
-                    )
-                    .unwrap(),
+                    &conversion::rev_reg_id(&did.id, &rev_reg_delta),
                     from,
                     to,
                 )
             }
+            LedgerObject::Txn(txn) => builder.build_get_txn_request(
+                None,
+                DOMAIN_LEDGER_ID,
+                i32::try_from(txn.seq_no).map_err(|_| DidIndyError::InvalidDidUrl)?,
+            ),
         }
     } else {
-        // TODO: Use with new get nym request
-
-        let _seq_no: Option<i64> = did
-            .query
-            .get(&QueryParameter::VersionId)
-            .and_then(|v| v.parse().ok());
-        let _timestamp: Option<i64> = did
-            .query
-            .get(&QueryParameter::VersionTime)
-            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
-            .and_then(|d| Some(d.timestamp()));
-
-        builder.build_get_nym_request(Option::None, &did.id)
+        let (seq_no, timestamp) = parse_nym_version(did)?;
+
+        builder
+            .build_get_nym_request(Option::None, &did.id)
+            .map(|request| with_version(request, seq_no, timestamp))
     };
     request.map_err(|e| DidIndyError::from(e))
 }
 
-fn handle_request<T: Pool>(pool: &T, request: &PreparedRequest) -> DidIndyResult<String> {
-    let (result, _timing) = block_on(request_transaction(pool, &request))?;
+// Parses versionId and versionTime of a DID URL without path. Only one of both may be set.
+fn parse_nym_version(did: &DidUrl) -> DidIndyResult<(Option<i32>, Option<i64>)> {
+    let seq_no = match did.query.get(&QueryParameter::VersionId) {
+        Some(version_id) => Some(
+            version_id
+                .parse::<i32>()
+                .map_err(|_| DidIndyError::InvalidDidUrl)?,
+        ),
+        None => None,
+    };
+    let timestamp = match did.query.get(&QueryParameter::VersionTime) {
+        Some(version_time) => Some(DateTime::parse_from_rfc3339(version_time)?.timestamp()),
+        None => None,
+    };
+
+    if seq_no.is_some() && timestamp.is_some() {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+    Ok((seq_no, timestamp))
+}
+
+// The request builder of indy-vdr does not support the seqNo and timestamp parameters
+// of GET_NYM and GET_ATTR yet, so they are added to the operation directly.
+fn with_version(
+    mut request: PreparedRequest,
+    seq_no: Option<i32>,
+    timestamp: Option<i64>,
+) -> PreparedRequest {
+    if let Some(seq_no) = seq_no {
+        request.req_json["operation"]["seqNo"] = Value::from(seq_no);
+    }
+    if let Some(timestamp) = timestamp {
+        request.req_json["operation"]["timestamp"] = Value::from(timestamp);
+    }
+    request
+}
+
+async fn handle_request<T: Pool>(pool: &T, request: &PreparedRequest) -> DidIndyResult<String> {
+    let (result, _timing) = request_transaction(pool, request).await?;
     match result {
         RequestResult::Reply(data) => Ok(data),
         RequestResult::Failed(error) => {
@@ -311,6 +1172,73 @@ async fn request_transaction<T: Pool>(
     perform_ledger_request(pool, &request).await
 }
 
+// Dereferencing a DID without path returns its DID document, the verification method or
+// service identified by the fragment, or the endpoint URL of the selected service
+fn dereference_did_document(doc: Value, did: &DidUrl) -> DidIndyResult<(Value, &'static str)> {
+    if let Some(service) = did.query.get(&QueryParameter::Service) {
+        let relative_ref = did.query.get(&QueryParameter::RelativeRef);
+        let endpoint = service_endpoint(&doc, service, relative_ref.map(String::as_str))?;
+        return Ok((Value::String(endpoint), TEXT_URI_LIST));
+    }
+    if did.query.contains_key(&QueryParameter::RelativeRef) {
+        return Err(DidIndyError::InvalidDidUrl);
+    }
+
+    let content_type = did_document_content_type(&doc);
+    match &did.fragment {
+        Some(fragment) => Ok((
+            find_by_fragment(&doc, fragment).ok_or(DidIndyError::NotFound)?,
+            content_type,
+        )),
+        None => Ok((doc, content_type)),
+    }
+}
+
+// Only documents with a JSON-LD context are served as JSON-LD
+fn did_document_content_type(doc: &Value) -> &'static str {
+    if doc.get("@context").is_some() {
+        DID_LD_JSON
+    } else {
+        DID_JSON
+    }
+}
+
+// Returns how long a result may be cached, None if it must not be cached at all
+fn cache_expiry(did: &DidUrl, ttl: Duration) -> Option<Expiry> {
+    let path = match &did.path {
+        Some(path) => path,
+        None => return Some(Expiry::After(ttl)),
+    };
+    match LedgerObject::from_str(path).ok()? {
+        LedgerObject::Schema(_) | LedgerObject::Txn(_) => Some(Expiry::Never),
+        // Can be updated by their submitter, e.g. the tails location of a registry
+        LedgerObject::ClaimDef(_) | LedgerObject::RevRegDef(_) => Some(Expiry::After(ttl)),
+        // Without versionTime the entry valid now is returned, which changes with revocations
+        LedgerObject::RevRegEntry(_)
+            if !did.query.contains_key(&QueryParameter::From)
+                && !did.query.contains_key(&QueryParameter::To) =>
+        {
+            let version_time = did.query.get(&QueryParameter::VersionTime)?;
+            let timestamp = DateTime::parse_from_rfc3339(version_time).ok()?;
+            if timestamp.timestamp() <= Utc::now().timestamp() {
+                Some(Expiry::Never)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_state_proof(ledger_data: &str) -> StateProof {
+    let v: Value = serde_json::from_str(ledger_data).unwrap_or_default();
+    if v["result"]["state_proof"]["multi_signature"].is_object() {
+        StateProof::Present
+    } else {
+        StateProof::Missing
+    }
+}
+
 fn parse_ledger_data(ledger_data: &str) -> DidIndyResult<Value> {
     let v: Value = serde_json::from_str(&ledger_data)?;
     let data: &Value = &v["result"]["data"];
@@ -321,6 +1249,51 @@ fn parse_ledger_data(ledger_data: &str) -> DidIndyResult<Value> {
     }
 }
 
+// Returns seqNo and txnTime of the transaction a ledger reply is based on
+fn parse_version(ledger_data: &str) -> (Option<u64>, Option<i64>) {
+    match serde_json::from_str::<Value>(ledger_data) {
+        // GET_TXN replies carry both in the metadata of the returned transaction
+        Ok(v) => (
+            v["result"]["seqNo"]
+                .as_u64()
+                .or_else(|| v["result"]["data"]["txnMetadata"]["seqNo"].as_u64()),
+            v["result"]["txnTime"]
+                .as_i64()
+                .or_else(|| v["result"]["data"]["txnMetadata"]["txnTime"].as_i64()),
+        ),
+        Err(_) => (None, None),
+    }
+}
+
+// Returns the schema DID URL of a GET_TXN reply holding a SCHEMA transaction
+fn parse_schema_txn(namespace: &str, txn_data: &Value) -> DidIndyResult<String> {
+    let txn = &txn_data["txn"];
+    if txn["type"].as_str() != Some(constants::SCHEMA) {
+        return Err(DidIndyError::NotFound);
+    }
+    let schema = &txn["data"]["data"];
+    match (
+        txn["metadata"]["from"].as_str(),
+        schema["name"].as_str(),
+        schema["version"].as_str(),
+    ) {
+        (Some(issuer), Some(name), Some(version)) => {
+            Ok(conversion::schema_did_url(namespace, issuer, name, version))
+        }
+        _ => Err(DidIndyError::UnexpectedLedgerData),
+    }
+}
+
+fn qualify_did(namespace: &str, did: &str) -> String {
+    format!("did:indy:{}:{}", namespace, did)
+}
+
+fn format_timestamp(timestamp: i64) -> Option<String> {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
 fn parse_or_now(datetime: Option<&String>) -> DidIndyResult<i64> {
     match datetime {
         Some(datetime) => {
@@ -340,6 +1313,7 @@ mod tests {
     use rstest::*;
 
     use indy_vdr::pool::ProtocolVersion;
+    use serde_json::json;
 
     #[fixture]
     fn request_builder() -> RequestBuilder {
@@ -444,6 +1418,296 @@ mod tests {
         assert!(to >= now);
     }
 
+    #[rstest]
+    fn build_get_nym_request_with_version_id(request_builder: RequestBuilder) {
+        let did_url =
+            DidUrl::from_str("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp?versionId=42").unwrap();
+        let request = build_request(&did_url, &request_builder).unwrap();
+        let operation = (*(request.req_json).get("operation").unwrap()).clone();
+
+        assert_eq!(request.txn_type, constants::GET_NYM);
+        assert_eq!(operation.get("seqNo").unwrap().as_i64().unwrap(), 42);
+        assert!(operation.get("timestamp").is_none());
+    }
+
+    #[rstest]
+    fn build_get_nym_request_with_version_time(request_builder: RequestBuilder) {
+        let datetime_as_str = "2020-12-20T19:17:47Z";
+        let did_url_as_str = format!(
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp?versionTime={}",
+            datetime_as_str
+        );
+        let did_url = DidUrl::from_str(&did_url_as_str).unwrap();
+        let request = build_request(&did_url, &request_builder).unwrap();
+        let operation = (*(request.req_json).get("operation").unwrap()).clone();
+
+        assert_eq!(request.txn_type, constants::GET_NYM);
+        assert_eq!(
+            operation.get("timestamp").unwrap().as_i64().unwrap(),
+            DateTime::parse_from_rfc3339(datetime_as_str)
+                .unwrap()
+                .timestamp()
+        );
+        assert!(operation.get("seqNo").is_none());
+    }
+
+    #[rstest]
+    fn build_get_nym_request_without_version(request_builder: RequestBuilder) {
+        let did_url = DidUrl::from_str("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp").unwrap();
+        let request = build_request(&did_url, &request_builder).unwrap();
+        let operation = (*(request.req_json).get("operation").unwrap()).clone();
+
+        assert!(operation.get("seqNo").is_none());
+        assert!(operation.get("timestamp").is_none());
+    }
+
+    #[rstest]
+    fn build_get_nym_request_fails_with_unparsable_version_id(request_builder: RequestBuilder) {
+        let did_url =
+            DidUrl::from_str("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp?versionId=latest").unwrap();
+        let request = build_request(&did_url, &request_builder);
+
+        assert!(matches! {
+            request, Err(DidIndyError::InvalidDidUrl)
+        })
+    }
+
+    #[rstest]
+    fn build_get_nym_request_fails_with_version_id_and_version_time(
+        request_builder: RequestBuilder,
+    ) {
+        let did_url = DidUrl::from_str(
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp?versionId=42&versionTime=2020-12-20T19:17:47Z",
+        )
+        .unwrap();
+        let request = build_request(&did_url, &request_builder);
+
+        assert!(matches! {
+            request, Err(DidIndyError::InvalidDidUrl)
+        })
+    }
+
+    #[test]
+    fn serialize_resolution_result() {
+        let result = ResolutionResult {
+            did_resolution_metadata: ResolutionMetadata::new(DID_JSON),
+            did_document: Some(json!({ "id": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp" })),
+            did_document_metadata: Some(ContentMetadata {
+                node_response: json!({ "op": "REPLY" }),
+                object_type: String::from("NYM"),
+                ..Default::default()
+            }),
+        };
+
+        let serialized = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            serialized["didDocument"]["id"],
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp"
+        );
+        assert_eq!(serialized["didDocumentMetadata"]["objectType"], "NYM");
+        assert!(serialized["didResolutionMetadata"].get("error").is_none());
+
+        let deserialized: ResolutionResult = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized, result);
+        assert_eq!(
+            deserialized.did_document_metadata().unwrap().object_type(),
+            "NYM"
+        );
+    }
+
+    #[test]
+    fn serialize_content_metadata_with_versions() {
+        let metadata = ContentMetadata {
+            node_response: json!({ "op": "REPLY" }),
+            object_type: String::from("NYM"),
+            created: format_timestamp(1608491867),
+            version_id: Some(String::from("42")),
+            ..Default::default()
+        };
+        let serialized = serde_json::to_value(&metadata).unwrap();
+
+        assert_eq!(serialized["created"], "2020-12-20T19:17:47Z");
+        assert_eq!(serialized["versionId"], "42");
+        assert!(serialized.get("nextVersionId").is_none());
+    }
+
+    #[test]
+    fn serialize_did_document_version() {
+        let version = DidDocumentVersion {
+            version_id: String::from("42"),
+            version_time: format_timestamp(1608491867),
+            did_document: json!({"id": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp"}),
+        };
+        assert_eq!(
+            serde_json::to_value(&version).unwrap(),
+            json!({
+                "versionId": "42",
+                "versionTime": "2020-12-20T19:17:47Z",
+                "didDocument": {"id": "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp"}
+            })
+        );
+    }
+
+    #[test]
+    fn nym_role_from_code() {
+        assert_eq!(NymRole::from_code(Some("0")), Some(NymRole::Trustee));
+        assert_eq!(NymRole::from_code(Some("2")), Some(NymRole::Steward));
+        assert_eq!(NymRole::from_code(Some("101")), Some(NymRole::Endorser));
+        assert_eq!(
+            NymRole::from_code(Some("201")),
+            Some(NymRole::NetworkMonitor)
+        );
+        assert_eq!(NymRole::from_code(None), Some(NymRole::None));
+        assert_eq!(NymRole::from_code(Some("42")), None);
+    }
+
+    #[test]
+    fn serialize_nym_metadata_with_namespaced_keys() {
+        let metadata = ContentMetadata {
+            object_type: String::from("NYM"),
+            role: Some(NymRole::NetworkMonitor),
+            creator: Some(String::from("did:indy:idunion:V4SGRU86Z58d6TV7PBUe6f")),
+            endorser: Some(String::from("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE")),
+            ..Default::default()
+        };
+        let serialized = serde_json::to_value(&metadata).unwrap();
+
+        assert_eq!(serialized["indy:role"], json!("NETWORK_MONITOR"));
+        assert_eq!(
+            serialized["indy:creator"],
+            json!("did:indy:idunion:V4SGRU86Z58d6TV7PBUe6f")
+        );
+        assert_eq!(
+            serialized["indy:endorser"],
+            json!("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE")
+        );
+    }
+
+    #[test]
+    fn parse_version_from_get_txn_reply() {
+        let reply = json!({
+            "op": "REPLY",
+            "result": {
+                "type": "3",
+                "data": {
+                    "txn": {"type": "101", "metadata": {"from": "Dk1fRRTtNazyMuK2cr64wp"}},
+                    "txnMetadata": {"seqNo": 10, "txnTime": 1608491867}
+                }
+            }
+        })
+        .to_string();
+        assert_eq!(parse_version(&reply), (Some(10), Some(1608491867)));
+    }
+
+    #[test]
+    fn parse_version_from_reply() {
+        let reply = json!({
+            "op": "REPLY",
+            "result": {
+                "type": "105",
+                "seqNo": 42,
+                "txnTime": 1608491867,
+                "data": "{}"
+            }
+        })
+        .to_string();
+
+        assert_eq!(parse_version(&reply), (Some(42), Some(1608491867)));
+        assert_eq!(parse_version("{}"), (None, None));
+    }
+
+    #[test]
+    fn parse_state_proof_present() {
+        let reply = json!({
+            "op": "REPLY",
+            "result": {
+                "data": "{}",
+                "state_proof": {
+                    "root_hash": "7Wdj3rrMCZ1R1M78H4xK5jxikmdUUGW2kbfJQ1HoEpK",
+                    "proof_nodes": "+QHo+FGAgICg0he/hjc9t/tPFzmCrb2T+nHnN0cRwqPKqZEc3pw2iCaAgICAgICgRlapIWgnsTzrx8KrbYNQ/L0/vM3KCUXOzBMqp0fbBqyAgICAgICAgPhxgIA=",
+                    "multi_signature": {
+                        "participants": ["Node1", "Node2", "Node3"],
+                        "signature": "RTyxbErBLcmTHBLj1rYCAEpMMkLnL65kchGni2tQczqzomYWZx9QQpLvnvNN5rD2nXkqaVW3USGak1vyAgvj2ecAKXQZXwcfosmnsBvRrH3M2M7cJeZSVWJCACfxMWuxAoMRtuaE2ABuDz6NFcUctXcSa4rdZFkxh5GoLYFqU4og6b",
+                        "value": {}
+                    }
+                }
+            }
+        })
+        .to_string();
+
+        assert_eq!(parse_state_proof(&reply), StateProof::Present);
+    }
+
+    #[test]
+    fn parse_state_proof_missing() {
+        let reply = json!({
+            "op": "REPLY",
+            "result": { "seqNo": 42, "data": "{}" }
+        })
+        .to_string();
+
+        assert_eq!(parse_state_proof(&reply), StateProof::Missing);
+    }
+
+    #[test]
+    fn cache_expiry_of_ledger_objects() {
+        let ttl = Duration::from_secs(60);
+        let expiry = |did_url: &str| cache_expiry(&DidUrl::from_str(did_url).unwrap(), ttl);
+
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp"),
+            Some(Expiry::After(ttl))
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4"),
+            Some(Expiry::Never)
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/CLAIM_DEF/104/default"),
+            Some(Expiry::After(ttl))
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54"),
+            Some(Expiry::After(ttl))
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54?versionTime=2020-12-20T19:17:47Z"),
+            Some(Expiry::Never)
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54"),
+            None
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54?versionTime=2999-12-20T19:17:47Z"),
+            None
+        );
+        assert_eq!(
+            expiry("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54?from=2019-12-20T19:17:47Z"),
+            None
+        );
+    }
+
+    #[test]
+    fn serialize_resolution_result_from_error() {
+        let result = ResolutionResult::from_error(&DidIndyError::EmptyData);
+        let serialized = serde_json::to_value(&result).unwrap();
+
+        assert_eq!(serialized["didResolutionMetadata"]["error"], "notFound");
+        assert_eq!(serialized["didResolutionMetadata"]["contentType"], DID_JSON);
+        assert_eq!(serialized["didDocument"], Value::Null);
+    }
+
+    #[test]
+    fn serialize_dereferencing_result_from_error() {
+        let result = DereferencingResult::from_error(&DidIndyError::InvalidDidUrl);
+        let serialized = serde_json::to_value(&result).unwrap();
+
+        assert_eq!(serialized["dereferencingMetadata"]["error"], "invalidDid");
+        assert_eq!(serialized["dereferencingMetadata"]["contentType"], JSON);
+        assert_eq!(serialized["contentStream"], Value::Null);
+    }
+
     #[rstest]
     fn build_get_schema_request_with_whitespace(request_builder: RequestBuilder) {
         let name = "My Schema";
@@ -462,4 +1726,152 @@ mod tests {
             .unwrap();
         assert_eq!(schema_name, name);
     }
+
+    #[test]
+    fn parse_schema_txn_returns_schema_did_url() {
+        let txn_data = json!({
+            "txn": {
+                "type": "101",
+                "data": {"data": {"attr_names": ["name"], "name": "npdb", "version": "4.3.4"}},
+                "metadata": {"from": "Dk1fRRTtNazyMuK2cr64wp"}
+            },
+            "txnMetadata": {"seqNo": 10}
+        });
+        assert_eq!(
+            parse_schema_txn("idunion", &txn_data).unwrap(),
+            "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4"
+        );
+    }
+
+    #[test]
+    fn parse_schema_txn_rejects_other_transactions() {
+        let txn_data = json!({
+            "txn": {"type": "1", "data": {"dest": "Dk1fRRTtNazyMuK2cr64wp"}}
+        });
+        assert!(matches!(
+            parse_schema_txn("idunion", &txn_data),
+            Err(DidIndyError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn status_list_request_only_for_rev_reg_entries() {
+        let request = |did_url: &str| status_list_request(&DidUrl::from_str(did_url).unwrap());
+
+        let rev_reg = request("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54?resourceType=anonCredsStatusList").unwrap();
+        assert_eq!(
+            rev_reg.map(|rev_reg| rev_reg.tag),
+            Some(String::from("a4e25e54"))
+        );
+
+        assert!(matches!(
+            request("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54"),
+            Ok(None)
+        ));
+        assert!(matches!(
+            request("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54?resourceType=other"),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+        assert!(matches!(
+            request("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_DEF/104/revocable/a4e25e54?resourceType=anonCredsStatusList"),
+            Err(DidIndyError::QueryParameterNotSupported)
+        ));
+        assert!(matches!(
+            request("did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/REV_REG_ENTRY/104/revocable/a4e25e54?resourceType=anonCredsStatusList&from=2019-12-20T19:17:47Z"),
+            Err(DidIndyError::QueryParameterNotSupported)
+        ));
+    }
+
+    #[test]
+    fn check_schema_did_url_for_cred_def_lookup() {
+        let check = |issuer: &str, schema: &str| {
+            check_schema_did_url(
+                &DidUrl::from_str(issuer).unwrap(),
+                &DidUrl::from_str(schema).unwrap(),
+            )
+        };
+        let schema = "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/SCHEMA/npdb/4.3.4";
+
+        assert!(check("did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE", schema).is_ok());
+        assert!(matches!(
+            check("did:indy:sovrin:BDrEcHc8Tb4Lb2VyQZWEDE", schema),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+        assert!(matches!(
+            check(
+                "did:indy:idunion:BDrEcHc8Tb4Lb2VyQZWEDE",
+                "did:indy:idunion:Dk1fRRTtNazyMuK2cr64wp/anoncreds/v0/CLAIM_DEF/104/default"
+            ),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+        assert!(matches!(
+            check(schema, schema),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+    }
+
+    #[test]
+    fn dereference_did_document_by_service_and_fragment() {
+        let doc = json!({
+            "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+            "verificationMethod": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey",
+                "type": "Ed25519VerificationKey2018",
+                "controller": "did:indy:idunion:QowxFtwciWceMFr7WbwnM",
+                "publicKeyBase58": "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk"
+            }],
+            "service": [{
+                "id": "did:indy:idunion:QowxFtwciWceMFr7WbwnM#did-communication",
+                "type": "did-communication",
+                "serviceEndpoint": "https://agent.com"
+            }]
+        });
+        let dereference = |did_url: &str| {
+            dereference_did_document(doc.clone(), &DidUrl::from_str(did_url).unwrap())
+        };
+
+        assert_eq!(
+            dereference("did:indy:idunion:QowxFtwciWceMFr7WbwnM?service=did-communication&relativeRef=/inbox").unwrap(),
+            (json!("https://agent.com/inbox"), TEXT_URI_LIST)
+        );
+        assert_eq!(
+            dereference("did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey").unwrap(),
+            (doc["verificationMethod"][0].clone(), DID_JSON)
+        );
+        assert_eq!(
+            dereference("did:indy:idunion:QowxFtwciWceMFr7WbwnM").unwrap(),
+            (doc.clone(), DID_JSON)
+        );
+        assert!(matches!(
+            dereference("did:indy:idunion:QowxFtwciWceMFr7WbwnM?relativeRef=/inbox"),
+            Err(DidIndyError::InvalidDidUrl)
+        ));
+        assert!(matches!(
+            dereference("did:indy:idunion:QowxFtwciWceMFr7WbwnM?service=unknown"),
+            Err(DidIndyError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn dereference_verification_method_with_keys() {
+        let doc = DidDocument::new(
+            "idunion",
+            "QowxFtwciWceMFr7WbwnM",
+            "67yDXtw6MK2D7V2kFSL7uMH6qTtrEbNtkdiTkbk9YJBk",
+            None,
+            None,
+        );
+        let did_url = DidUrl::from_str("did:indy:idunion:QowxFtwciWceMFr7WbwnM#verkey").unwrap();
+
+        let (method, content_type) = dereference_did_document(
+            doc.to_value_with_keys(KeyRepresentation::JsonWebKey2020)
+                .unwrap(),
+            &did_url,
+        )
+        .unwrap();
+
+        assert_eq!(method["type"], "JsonWebKey2020");
+        assert_eq!(method["publicKeyJwk"]["crv"], "Ed25519");
+        assert_eq!(content_type, DID_LD_JSON);
+    }
 }
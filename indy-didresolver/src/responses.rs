@@ -21,7 +21,8 @@ pub struct GetNymResultV0 {
     pub identifier: Option<DidValue>,
     pub dest: DidValue,
     pub role: Option<String>,
-    pub verkey: String,
+    // A NYM with verkey null is deactivated
+    pub verkey: Option<String>,
 }
 
 #[derive(Deserialize, Eq, PartialEq, Debug)]
@@ -30,7 +31,8 @@ pub struct GetNymResultV1 {
     pub identifier: Option<DidValue>,
     pub dest: DidValue,
     pub role: Option<String>,
-    pub verkey: String,
+    // A NYM with verkey null is deactivated
+    pub verkey: Option<String>,
     pub diddoc_content: Option<Value>,
 }
 